@@ -3,12 +3,39 @@ pub const GLOBAL_STATE_SEED : &[u8] = b"global-state";
 
 pub const USER_ACCOUNT_SEED: &[u8] = b"user_account";
 
+pub const GAME_SEED: &[u8] = b"game";
+pub const GAME_VAULT_SEED: &[u8] = b"game-vault";
+pub const BET_COMMITMENT_SEED: &[u8] = b"bet-commitment";
+pub const LIMIT_ORDER_SEED: &[u8] = b"limit-order";
+
 pub const ETH_ORACLE: &str = "JBu1AL4obBcCMqKBBxhpWCNUt136ijcuMZLFvTP7iWdB";
 pub const SOL_ORACLE: &str = "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG";
 
+// the pyth receiver program posts verified price updates, used to challenge a resolution with
+// the price at the scheduled end
+pub const PYTH_RECEIVER: &str = "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ";
+// the anchor discriminator of the receiver's PriceUpdateV2 accounts
+pub const PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+// the pyth feed ids of SOL/USD and ETH/USD
+pub const SOL_FEED_ID: [u8; 32] = [
+    0xef, 0x0d, 0x8b, 0x6f, 0xda, 0x2c, 0xeb, 0xa4, 0x1d, 0xa1, 0x5d, 0x40, 0x95, 0xd1, 0xda, 0x39,
+    0x2a, 0x0d, 0x2f, 0x8e, 0xd0, 0xc6, 0xc7, 0xbc, 0x0f, 0x4c, 0xfa, 0xc8, 0xc2, 0x80, 0xb5, 0x6d,
+];
+pub const ETH_FEED_ID: [u8; 32] = [
+    0xff, 0x61, 0x49, 0x1a, 0x93, 0x11, 0x12, 0xdd, 0xf1, 0xbd, 0x81, 0x47, 0xcd, 0x1b, 0x64, 0x13,
+    0x75, 0xf7, 0x9f, 0x58, 0x25, 0x12, 0x6d, 0x66, 0x54, 0x80, 0x87, 0x46, 0x34, 0xfd, 0x0a, 0xce,
+];
+
 
 pub const ADMIN_WALLETS : &str= &"5GrCgeZRNtGgKe7ezhSo5vU6ug68JsrC1FCo9246DBgg";
 
 pub const MARGIN_OF_ERROR : u64= 1;
 
 pub const REDEEMER_WALLET : &str = &"F25WqA7xPLZboJ9Ydad4Z9wGrw25gawkK9jai8nbRsr7";
+
+// seconds after a game should have been resolved before bettors can refund themselves
+pub const EMERGENCY_REFUND_TIMEOUT: u64 = 60 * 60;
+
+// space allocated for the global state, leaving some extra for future upgrades. Deployments
+// whose global state is smaller have to grow it with change_account_size before upgrading.
+pub const GLOBAL_STATE_SIZE: usize = 1000;
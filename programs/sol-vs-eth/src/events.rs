@@ -0,0 +1,224 @@
+use anchor_lang::prelude::*;
+
+#[event]
+pub struct GameStarted {
+    pub game: Pubkey,
+    pub game_vault: Pubkey,
+    pub betting_start: u64,
+    pub betting_time: u64,
+    // rolled over from previous draws
+    pub rollover_bonus: u64,
+    // put by the house on the outcomes of a race or bucket market
+    pub house_seed: u64,
+}
+
+#[event]
+pub struct CrankRewardPaid {
+    pub game: Pubkey,
+    pub cranker: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct BetPlaced {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub total_user_bet: u64,
+    pub fee: u64,
+    // the amount the house matched on the opposite side of this bet
+    pub house_matched: u64,
+    pub sol_bet_size: u64,
+    pub eth_bet_size: u64,
+}
+
+#[event]
+pub struct BetCancelled {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub fee: u64,
+    // house match given back to the house wallet
+    pub house_unmatched: u64,
+    pub sol_bet_size: u64,
+    pub eth_bet_size: u64,
+}
+
+#[event]
+pub struct BetCommitted {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+// accepted is false when the house couldn't cover the bet and the stake got refunded
+#[event]
+pub struct BetRevealed {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub accepted: bool,
+}
+
+#[event]
+pub struct CommitmentRefunded {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct LimitOrderPlaced {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub min_multiplier: f64,
+    pub fee: u64,
+}
+
+// emitted for every order at the anticipation, filled or not
+#[event]
+pub struct LimitOrderSettled {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub filled: bool,
+}
+
+#[event]
+pub struct LimitOrderWithdrawn {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct AnticipationStarted {
+    pub game: Pubkey,
+    pub anticipating_start: u64,
+    pub initial_sol_price: u64,
+    pub initial_eth_price: u64,
+    pub sol_bet_size: u64,
+    pub eth_bet_size: u64,
+    // how much the house added to the pool to reach the minimum multiplier
+    pub house_top_up: u64,
+    pub scheduled_end: u64,
+}
+
+#[event]
+pub struct BetCashedOut {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub payout: u64,
+    pub sol_price: u64,
+    pub eth_price: u64,
+}
+
+#[event]
+pub struct GameResolved {
+    pub game: Pubkey,
+    pub anticipating_end: u64,
+    pub final_sol_price: u64,
+    pub final_eth_price: u64,
+    /// 0 sol, 1 eth, 2 draw
+    pub winner: u8,
+    // resolved too late, prices fell back to the initial ones
+    pub voided: bool,
+    // with the settlement jitter drawn at the resolution
+    pub scheduled_end: u64,
+    // the resolution can be challenged until then
+    pub dispute_end: u64,
+}
+
+// the house took its share, bets can be claimed
+#[event]
+pub struct GameFinalized {
+    pub game: Pubkey,
+    pub final_sol_price: u64,
+    pub final_eth_price: u64,
+    pub winner: u8,
+    pub amount_owed_to_winners: u64,
+    pub won_by_house: u64,
+    // carried to the next game by the draw policy
+    pub rolled_over: u64,
+}
+
+#[event]
+pub struct ResolutionChallenged {
+    pub game: Pubkey,
+    pub challenger: Pubkey,
+    pub successful: bool,
+    pub final_sol_price: u64,
+    pub final_eth_price: u64,
+    // the bond goes to the house when the challenge fails
+    pub bond_forfeited: u64,
+    // a successful challenge reopens the dispute window until then
+    pub dispute_end: u64,
+}
+
+#[event]
+pub struct WinningsPaid {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct BetClaimed {
+    pub game: Pubkey,
+    pub user: Pubkey,
+    pub side: u8,
+    pub amount: u64,
+    pub payout: u64,
+}
+
+#[event]
+pub struct GameClosed {
+    pub game: Pubkey,
+    pub game_vault: Pubkey,
+    pub closed_by: Pubkey,
+}
+
+#[event]
+pub struct GameCancelled {
+    pub game: Pubkey,
+    // stakes returned to users in this call
+    pub refunded: u64,
+    // stake returned to the house once every user got refunded
+    pub house_refund: u64,
+    pub fully_refunded: bool,
+}
+
+#[event]
+pub struct ShopPurchase {
+    pub buyer: Pubkey,
+    pub discord_id: u64,
+    pub item_id: u8,
+    pub edition: u8,
+    pub price: u8,
+    pub quantity_left: u8,
+}
+
+#[event]
+pub struct AirdropClaimed {
+    pub receiver: Pubkey,
+    pub discord_id: u64,
+    pub airdrop: u16,
+    pub amount: u64,
+}
+
+#[event]
+pub struct SpinRegistered {
+    pub user: Pubkey,
+    pub reward: u16,
+    pub total_reward: u16,
+    pub spin_time: u64,
+}
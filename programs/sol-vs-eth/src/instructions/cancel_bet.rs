@@ -0,0 +1,114 @@
+use std::str::FromStr;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, USER_ACCOUNT_SEED};
+use crate::events::BetCancelled;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState, User};
+use crate::utils::transfer_tokens;
+
+/// Takes the whole bet of the signer back out of the pool while the betting is still open.
+/// The stake is returned minus the cancellation fee, and the house match it got goes back to the
+/// house wallet.
+pub fn handle_cancel_bet(ctx: Context<CancelBet>) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &mut ctx.accounts.global_state;
+
+    game.require_status(GameStatus::Betting)?;
+    require!(
+        game.betting_active(global_state.betting_time)?,
+        QuickBetsErrors::BettingInactive
+    );
+
+    let user_bet = game.remove_user_bet(ctx.accounts.signer.key())?;
+    ctx.accounts.user_account.remove_volume(user_bet.amount);
+    global_state.unrecord_bet(
+        &game.market.market_type,
+        user_bet.side,
+        user_bet.amount,
+        user_bet.placements,
+    );
+
+    // the bets left in the pool still need their multiplier guaranteed
+    global_state.reserve_top_up(game, ctx.accounts.house_wallet.amount + user_bet.house_matched)?;
+
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+
+    let fee =
+        (user_bet.amount as f64 * (global_state.cancellation_fee as f64 / 100.0 / 100.0)) as u64;
+    transfer_tokens(
+        ctx.accounts.game_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        user_bet.amount - fee,
+        Some(seeds),
+    )?;
+    if fee > 0 {
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.fees_wallet.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            fee,
+            Some(seeds),
+        )?;
+        global_state.stats.fees_collected += fee;
+    }
+    if user_bet.house_matched > 0 {
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.house_wallet.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            user_bet.house_matched,
+            Some(seeds),
+        )?;
+        global_state.stats.house_matched_volume -= user_bet.house_matched;
+    }
+
+    emit!(BetCancelled {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        side: user_bet.side,
+        amount: user_bet.amount,
+        fee,
+        house_unmatched: user_bet.house_matched,
+        sol_bet_size: game.sol_bet_size,
+        eth_bet_size: game.eth_bet_size,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CancelBet<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = Pubkey::from_str("3kg54CAWcdq2gPCGjQ534brznAQpWHpJP1mpCA3DavZT").unwrap())]
+    pub fees_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, token::authority = signer)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, signer.key.as_ref()], bump)]
+    pub user_account: Account<'info, User>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+    pub token_program: Program<'info, Token>,
+}
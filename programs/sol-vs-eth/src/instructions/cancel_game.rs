@@ -0,0 +1,115 @@
+use std::str::FromStr;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{ADMIN_WALLETS, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::{BetClaimed, GameCancelled};
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState};
+use crate::utils::transfer_tokens;
+
+/// Stops a stuck game and refunds every bet. Limit orders and commitments are refunded by their
+/// owners with withdraw_limit_order and refund_commitment, which close their accounts.
+/// The remaining accounts are the token accounts of the bettors to refund, it can be called
+/// several times if they don't fit in one transaction. Once every user is refunded, whatever is
+/// left in the vault is the house stake and goes back to the house wallet.
+pub fn handle_cancel_game<'info>(ctx: Context<'_, '_, '_, 'info, CancelGame<'info>>) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+
+    if game.status != GameStatus::Cancelled {
+        game.transition(GameStatus::Cancelled, &ctx.accounts.global_state)?;
+        ctx.accounts.global_state.release_top_up(game);
+    }
+
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+
+    let mut refunded = 0;
+    for account in ctx.remaining_accounts.iter() {
+        let receiver = Account::<TokenAccount>::try_from(account)?;
+        let user_bet = if let Some(user_bet) = game.get_user_bet(receiver.owner) {
+            user_bet
+        } else {
+            continue;
+        };
+        if user_bet.claimed {
+            continue;
+        }
+
+        game.mark_bet_claimed(receiver.owner)?;
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            account.clone(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            user_bet.amount,
+            Some(seeds),
+        )?;
+        refunded += user_bet.amount;
+
+        emit!(BetClaimed {
+            game: game.key(),
+            user: receiver.owner,
+            side: user_bet.side,
+            amount: user_bet.amount,
+            payout: user_bet.amount,
+        });
+    }
+
+    let fully_refunded = game.check_all_bets_claimed();
+    let mut house_refund = 0;
+    if fully_refunded {
+        // the bonus rolled over from previous draws goes back to the rollover pool for the next
+        // game, it's held in the house wallet as well
+        let rollover_bonus = std::mem::take(&mut game.rollover_bonus);
+        ctx.accounts.global_state.rollover_pool += rollover_bonus;
+        house_refund = ctx.accounts.game_vault.amount - refunded - rollover_bonus;
+        if house_refund + rollover_bonus > 0 {
+            transfer_tokens(
+                ctx.accounts.game_vault.to_account_info(),
+                ctx.accounts.house_wallet.to_account_info(),
+                ctx.accounts.global_auth_pda.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                house_refund + rollover_bonus,
+                Some(seeds),
+            )?;
+        }
+        ctx.accounts.global_state.remove_game_record(game.key());
+    } else {
+        ctx.accounts
+            .global_state
+            .modify_game_record(game.key(), GameStatus::Cancelled);
+    }
+
+    emit!(GameCancelled {
+        game: game.key(),
+        refunded,
+        house_refund,
+        fully_refunded,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CancelGame<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
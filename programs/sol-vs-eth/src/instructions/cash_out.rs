@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::BetCashedOut;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState};
+use crate::utils::{get_game_prices, transfer_tokens};
+
+/// Sells the signer's bet to the house during the anticipation, at the value the cash-out model
+/// gives it with the current prices. The house wallet pays it and the bet's winnings, if any, stay
+/// in the vault for the house.
+pub fn handle_cash_out(ctx: Context<CashOut>, min_payout: u64) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &mut ctx.accounts.global_state;
+
+    require!(global_state.cash_out_enabled, QuickBetsErrors::CashOutDisabled);
+    game.require_status(GameStatus::Anticipation)?;
+    // the cash-out model only values two sided markets
+    require!(
+        !game.market.market_type.pooled_by_outcome(),
+        QuickBetsErrors::UnsupportedMarket
+    );
+    require!(
+        !game.anticipation_over()?,
+        QuickBetsErrors::InvalidGameStatus
+    );
+
+    let user_bet = if let Some(user_bet) = game.get_user_bet(ctx.accounts.signer.key()) {
+        user_bet
+    } else {
+        return err!(QuickBetsErrors::NoBetFound);
+    };
+    require!(!user_bet.claimed, QuickBetsErrors::AlreadyClaimed);
+
+    let (sol_price, eth_price) = get_game_prices(&ctx.accounts.sol_feed, &ctx.accounts.eth_feed)?;
+    let payout = game.cash_out_value(&user_bet, sol_price, eth_price, global_state);
+    require!(payout >= min_payout, QuickBetsErrors::SlippageExceeded);
+    // the top ups reserved for the other running games aren't the house's to pay out
+    require!(
+        payout <= global_state.house_available_for(game, ctx.accounts.house_wallet.amount),
+        QuickBetsErrors::HouseBankrupt
+    );
+
+    game.mark_cashed_out(ctx.accounts.signer.key())?;
+    global_state.stats.cash_out_paid += payout;
+    global_state.stats.house_pnl -= payout as i64;
+
+    if payout > 0 {
+        let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+        let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+        transfer_tokens(
+            ctx.accounts.house_wallet.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            payout,
+            Some(seeds),
+        )?;
+    }
+
+    emit!(BetCashedOut {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        side: user_bet.side,
+        amount: user_bet.amount,
+        payout,
+        sol_price,
+        eth_price,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CashOut<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, token::authority = signer, constraint = receiver.mint == house_wallet.mint)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Checking this manually in the instruction
+    pub sol_feed: AccountInfo<'info>,
+    /// CHECK: Checking this manually in the instruction
+    pub eth_feed: AccountInfo<'info>,
+
+    pub token_program: Program<'info, Token>,
+}
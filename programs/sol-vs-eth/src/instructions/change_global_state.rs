@@ -2,8 +2,9 @@ use std::str::FromStr;
 
 use anchor_lang::prelude::*;
 
-use crate::consts::ADMIN_WALLETS;
-use crate::state::GlobalState;
+use crate::consts::{ADMIN_WALLETS, GLOBAL_STATE_SEED};
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{GlobalState, MarketConfig, MarketType, PayoutMode};
 
 pub fn handle_change_global_state(
     ctx: Context<ChangeGlobalState>,
@@ -15,6 +16,8 @@ pub fn handle_change_global_state(
     min_multiplier: f64,
     max_house_bet_size : u64
 ) -> Result<()> {
+    // above 2x both sides can't be guaranteed at once
+    require!(min_multiplier <= 2.0, QuickBetsErrors::InvalidSize);
     let global_state = &mut ctx.accounts.global_state;
 
     global_state.betting_fees = betting_fees;
@@ -34,6 +37,142 @@ pub fn handle_change_global_state(
     Ok(())
 }
 
+pub fn handle_change_crank_rewards(
+    ctx: Context<ChangeCrankRewards>,
+    crank_reward: u64,
+    crank_fee_share: u64,
+) -> Result<()> {
+    require!(crank_fee_share <= 10_000, QuickBetsErrors::InvalidSize);
+    let global_state = &mut ctx.accounts.global_state;
+
+    global_state.crank_reward = crank_reward;
+    global_state.crank_fee_share = crank_fee_share;
+    Ok(())
+}
+
+pub fn handle_change_max_games_in_progress(
+    ctx: Context<ChangeMaxGamesInProgress>,
+    max_games_in_progress: u8,
+) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+    // a running game must never be rotated out of the records
+    require!(
+        (max_games_in_progress as usize) < global_state.game_records.len(),
+        QuickBetsErrors::InvalidSize
+    );
+
+    global_state.max_games_in_progress = max_games_in_progress;
+    Ok(())
+}
+
+pub fn handle_change_cancellation_fee(
+    ctx: Context<ChangeCancellationFee>,
+    cancellation_fee: u64,
+) -> Result<()> {
+    require!(cancellation_fee <= 10_000, QuickBetsErrors::InvalidSize);
+    ctx.accounts.global_state.cancellation_fee = cancellation_fee;
+    Ok(())
+}
+
+pub fn handle_change_cash_out_model(
+    ctx: Context<ChangeCashOutModel>,
+    cash_out_enabled: bool,
+    cash_out_sensitivity: u64,
+    cash_out_haircut: u64,
+) -> Result<()> {
+    require!(cash_out_haircut <= 10_000, QuickBetsErrors::InvalidSize);
+    let global_state = &mut ctx.accounts.global_state;
+
+    global_state.cash_out_enabled = cash_out_enabled;
+    global_state.cash_out_sensitivity = cash_out_sensitivity;
+    global_state.cash_out_haircut = cash_out_haircut;
+    Ok(())
+}
+
+/// only applies to the games started afterwards.
+pub fn handle_change_commit_reveal(
+    ctx: Context<ChangeCommitReveal>,
+    commit_reveal: bool,
+    reveal_time: u64,
+    forfeit_unrevealed: bool,
+) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+
+    global_state.commit_reveal = commit_reveal;
+    global_state.reveal_time = reveal_time;
+    global_state.forfeit_unrevealed = forfeit_unrevealed;
+    Ok(())
+}
+
+pub fn handle_change_settlement_timing(
+    ctx: Context<ChangeSettlementTiming>,
+    settlement_jitter: u64,
+    publish_time_tolerance: u64,
+) -> Result<()> {
+    require!(publish_time_tolerance > 0, QuickBetsErrors::PublishTimeToleranceUnset);
+    let global_state = &mut ctx.accounts.global_state;
+
+    global_state.settlement_jitter = settlement_jitter;
+    global_state.publish_time_tolerance = publish_time_tolerance;
+    Ok(())
+}
+
+pub fn handle_change_dispute_settings(
+    ctx: Context<ChangeDisputeSettings>,
+    dispute_window: u64,
+    challenge_bond: u64,
+) -> Result<()> {
+    let global_state = &mut ctx.accounts.global_state;
+
+    global_state.dispute_window = dispute_window;
+    global_state.challenge_bond = challenge_bond;
+    Ok(())
+}
+
+/// only applies to the games started afterwards.
+pub fn handle_set_market_config(ctx: Context<SetMarketConfig>, market: MarketConfig) -> Result<()> {
+    require!(market.draw_fee <= 10_000, QuickBetsErrors::InvalidSize);
+    require!(market.late_bet_decay <= 10_000, QuickBetsErrors::InvalidSize);
+    require!(market.up_down_asset <= 1, QuickBetsErrors::InvalidSide);
+    if market.market_type == MarketType::Race {
+        // a race runs over 3 to 8 feeds
+        require!(
+            (3..=8).contains(&market.race_feed_count),
+            QuickBetsErrors::InvalidSide
+        );
+        require!(
+            market.race_feeds[..market.race_feed_count as usize]
+                .iter()
+                .all(|feed| *feed != Pubkey::default()),
+            QuickBetsErrors::InvalidOracle
+        );
+    }
+    if market.market_type == MarketType::Buckets {
+        // 2 to 8 buckets, between increasing edges
+        require!(
+            (2..=8).contains(&market.bucket_count),
+            QuickBetsErrors::InvalidSide
+        );
+        require!(
+            market.bucket_edges[..market.bucket_count as usize - 1]
+                .windows(2)
+                .all(|edges| edges[0] < edges[1]),
+            QuickBetsErrors::InvalidSize
+        );
+    }
+    if market.payout_mode == PayoutMode::FixedOdds {
+        // quoted on two sided markets only, and a quote has to pay more than the stake
+        require!(
+            !market.market_type.pooled_by_outcome(),
+            QuickBetsErrors::UnsupportedMarket
+        );
+        require!(market.fixed_multiplier > 1.0, QuickBetsErrors::InvalidSize);
+        require!(market.fixed_odds_skew <= 10_000, QuickBetsErrors::InvalidSize);
+    }
+    ctx.accounts.global_state.market = market;
+    Ok(())
+}
+
 #[derive(Accounts)]
 pub struct ChangeGlobalState<'info> {
     #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
@@ -43,3 +182,67 @@ pub struct ChangeGlobalState<'info> {
     /// CHECK: can techincally be any account
     pub new_crank_admin: AccountInfo<'info>,
 }
+
+#[derive(Accounts)]
+pub struct ChangeCrankRewards<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeMaxGamesInProgress<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeCancellationFee<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeCashOutModel<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeCommitReveal<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeSettlementTiming<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct ChangeDisputeSettings<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMarketConfig<'info> {
+    #[account(mut, address = Pubkey::from_str(ADMIN_WALLETS).unwrap())]
+    pub signer: Signer<'info>,
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+}
@@ -3,8 +3,9 @@ use anchor_spl::token;
 use anchor_spl::token::{Token, TokenAccount};
 
 use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::GameClosed;
 use crate::quick_bets_errors::QuickBetsErrors;
-use crate::state::{Game, GlobalAuth, GlobalState};
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState};
 use crate::utils::transfer_tokens;
 // use crate::utils::transfer_tokens;
 // use crate::utils::transfer_tokens;
@@ -23,6 +24,8 @@ pub fn handle_close_game(ctx: Context<CloseGame>) -> Result<()> {
 
     ctx.accounts.global_state.to_close = Pubkey::default();
 
+    game.transition(GameStatus::Closed, &ctx.accounts.global_state)?;
+
     // let bump = *ctx.bumps.get("global_auth_pda").unwrap();
     // let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
     // transfer_tokens(
@@ -34,9 +37,6 @@ pub fn handle_close_game(ctx: Context<CloseGame>) -> Result<()> {
     //     Some(seeds),
     // )?;
 
-
-    require!(game.check_all_bets_claimed(), QuickBetsErrors::BetsNotClaimed);
-
     // require!(ctx.accounts.game_vault.amount == 0, QuickBetsErrors::VaultNotEmpty);
 
     let cpi_accounts = token::CloseAccount {
@@ -52,6 +52,12 @@ pub fn handle_close_game(ctx: Context<CloseGame>) -> Result<()> {
 
     token::close_account(cpi_ctx)?;
 
+    emit!(GameClosed {
+        game: game.key(),
+        game_vault: ctx.accounts.game_vault.key(),
+        closed_by: ctx.accounts.signer.key(),
+    });
+
     Ok(())
 }
 
@@ -70,7 +76,7 @@ pub struct CloseGame<'info> {
     #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
     pub global_state: Account<'info, GlobalState>,
 
-    #[account(mut)]
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
     pub game_vault: Account<'info, TokenAccount>,
 
     #[account(mut)]
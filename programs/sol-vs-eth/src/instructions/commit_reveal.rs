@@ -0,0 +1,254 @@
+use std::mem::size_of;
+use std::str::FromStr;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{BET_COMMITMENT_SEED, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::{BetCommitted, BetRevealed, CommitmentRefunded};
+use crate::instructions::charge_betting_fee;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{BetCommitment, Game, GameStatus, GlobalAuth, GlobalState, PayoutMode};
+use crate::utils::transfer_tokens;
+
+/// Escrows a stake with a hidden side during the betting of a commit-reveal game.
+/// commitment is sha256(side, salt, signer), the fee is taken now. The commitment gets its own
+/// account, so a user can only commit once per game.
+pub fn handle_commit_bet(ctx: Context<CommitBet>, commitment: [u8; 32], amount: u64) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &mut ctx.accounts.global_state;
+
+    require!(game.commit_reveal, QuickBetsErrors::NotCommitRevealGame);
+    // fixed odds are quoted when the side is known
+    require!(
+        game.market.payout_mode != PayoutMode::FixedOdds,
+        QuickBetsErrors::UnsupportedMarket
+    );
+    game.require_status(GameStatus::Betting)?;
+    require!(
+        game.betting_active(global_state.betting_time)?,
+        QuickBetsErrors::BettingInactive
+    );
+    require!(
+        amount <= global_state.max_user_bet,
+        QuickBetsErrors::MaxUserBetExceeded
+    );
+
+    ctx.accounts.bet_commitment.set_inner(BetCommitment {
+        game: game.key(),
+        owner: ctx.accounts.signer.key(),
+        commitment,
+        amount,
+        placed_at: Clock::get()?.unix_timestamp as u64,
+    });
+    game.committed_amount += amount;
+
+    transfer_tokens(
+        ctx.accounts.payer.to_account_info(),
+        ctx.accounts.game_vault.to_account_info(),
+        ctx.accounts.signer.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        None,
+    )?;
+    let fee = charge_betting_fee(
+        global_state,
+        amount,
+        ctx.accounts.payer.to_account_info(),
+        ctx.accounts.fees_wallet.to_account_info(),
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.signer.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+    )?;
+    global_state.stats.fees_collected += fee;
+
+    emit!(BetCommitted {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        amount,
+        fee,
+    });
+    Ok(())
+}
+
+/// Reveals the side of a commitment once the betting closed, the stake joins the pool as a
+/// regular bet. If the house can't guarantee the min multiplier with it, the stake is refunded.
+/// The commitment account is closed either way.
+pub fn handle_reveal_bet(ctx: Context<RevealBet>, side: u8, salt: [u8; 32]) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &mut ctx.accounts.global_state;
+    let owner = ctx.accounts.signer.key();
+
+    require!(game.commit_reveal, QuickBetsErrors::NotCommitRevealGame);
+    game.require_status(GameStatus::Betting)?;
+    require!(game.reveal_active(global_state)?, QuickBetsErrors::RevealInactive);
+    require!(side < game.outcome_count(), QuickBetsErrors::InvalidSide);
+
+    let commitment = &ctx.accounts.bet_commitment;
+    commitment.check_reveal(side, salt)?;
+    let amount = commitment.amount;
+    game.committed_amount -= amount;
+
+    // weighted by when the stake was committed
+    let weight = game.bet_weight(commitment.placed_at, global_state.betting_time);
+    let (_, top_up) = game.required_top_up_with(side, amount, weight);
+    let accepted = top_up <= game.house_capacity_left()
+        && top_up <= global_state.house_available_for(game, ctx.accounts.house_wallet.amount);
+    if accepted {
+        game.add_to_side(side, amount);
+        // weighted by when the stake was committed
+        game.add_user_bet(
+            owner,
+            amount,
+            side,
+            commitment.placed_at,
+            global_state.betting_time,
+        )?;
+        global_state.reserve_top_up(game, ctx.accounts.house_wallet.amount)?;
+        global_state.record_bet(&game.market.market_type, side, amount, 0, 0);
+    } else {
+        let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+        let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            amount,
+            Some(seeds),
+        )?;
+    }
+
+    emit!(BetRevealed {
+        game: game.key(),
+        user: owner,
+        side,
+        amount,
+        accepted,
+    });
+    Ok(())
+}
+
+/// Returns the stake of a commitment that wasn't revealed, when the game doesn't forfeit them or
+/// got cancelled. The fee isn't refunded. Forfeited commitments only get their account closed.
+pub fn handle_refund_commitment(ctx: Context<RefundCommitment>) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let amount = game.refund_commitment(ctx.accounts.bet_commitment.amount)?;
+
+    if amount > 0 {
+        let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+        let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            amount,
+            Some(seeds),
+        )?;
+    }
+
+    emit!(CommitmentRefunded {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        amount,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CommitBet<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = size_of::<BetCommitment>() + 12,
+        seeds = [BET_COMMITMENT_SEED, game.key().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub bet_commitment: Box<Account<'info, BetCommitment>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = Pubkey::from_str("3kg54CAWcdq2gPCGjQ534brznAQpWHpJP1mpCA3DavZT").unwrap())]
+    pub fees_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevealBet<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(
+        mut,
+        seeds = [BET_COMMITMENT_SEED, game.key().as_ref(), signer.key().as_ref()],
+        bump,
+        close = signer
+    )]
+    pub bet_commitment: Box<Account<'info, BetCommitment>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    // refunded if the bet can't be accepted
+    #[account(mut, token::authority = signer)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RefundCommitment<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(
+        mut,
+        seeds = [BET_COMMITMENT_SEED, game.key().as_ref(), signer.key().as_ref()],
+        bump,
+        close = signer
+    )]
+    pub bet_commitment: Box<Account<'info, BetCommitment>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, token::authority = signer)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -0,0 +1,308 @@
+use std::mem::size_of;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::consts::{GAME_SEED, GAME_VAULT_SEED, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::{GameClosed, GameStarted};
+use crate::instructions::{anticipate_game, finalize_settlement, settle_game};
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState, PayoutMode};
+use crate::utils::{create_pda_account, pay_crank_reward, transfer_tokens};
+
+/// Looks at a running game of the series and performs whichever step is due: starting the
+/// anticipation, resolving it or finalizing it after the dispute window. Then starts the next game if the series allows it, which with
+/// overlapping games happens as soon as the current one enters anticipation.
+/// A finished game rotated out of the records can be passed along to be closed in the same tick.
+/// On a race the remaining accounts are its feeds, in the order of the outcomes. When the tick
+/// starts the anticipation they're followed by every open limit order of the game.
+pub fn handle_crank_tick<'info>(ctx: Context<'_, '_, '_, 'info, CrankTick<'info>>) -> Result<()> {
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+
+    let closed = close_finished_game(ctx.accounts, seeds)?;
+
+    // the game that moved forward in this tick
+    let mut cranked_game = None;
+    if let Some(game) = ctx.accounts.game.as_mut() {
+        require!(
+            ctx.accounts.global_state.is_recorded(game.key()),
+            QuickBetsErrors::GameMismatch
+        );
+        let game_vault = match ctx.accounts.game_vault.as_ref() {
+            Some(game_vault) if game_vault.key() == game.game_vault => game_vault,
+            _ => return err!(QuickBetsErrors::GameVaultMismatch),
+        };
+
+        match game.status {
+            GameStatus::Betting if game.betting_over(&ctx.accounts.global_state)? => {
+                anticipate_game(
+                    game,
+                    &mut ctx.accounts.global_state,
+                    &ctx.accounts.sol_feed,
+                    &ctx.accounts.eth_feed,
+                    ctx.remaining_accounts,
+                    game_vault,
+                    &ctx.accounts.house_wallet,
+                    ctx.accounts.global_auth_pda.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    seeds,
+                )?;
+                cranked_game = Some(game.key());
+            }
+            GameStatus::Anticipation if game.anticipation_over()? => {
+                settle_game(
+                    game,
+                    &mut ctx.accounts.global_state,
+                    &ctx.accounts.sol_feed,
+                    &ctx.accounts.eth_feed,
+                    ctx.remaining_accounts,
+                    &ctx.accounts.slot_hashes,
+                    game_vault,
+                    &ctx.accounts.house_wallet,
+                    ctx.accounts.global_auth_pda.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    seeds,
+                )?;
+                cranked_game = Some(game.key());
+            }
+            GameStatus::Resolved | GameStatus::Voided
+                if !game.finalized && game.dispute_over()? =>
+            {
+                finalize_settlement(
+                    game,
+                    &mut ctx.accounts.global_state,
+                    game_vault,
+                    &ctx.accounts.house_wallet,
+                    ctx.accounts.global_auth_pda.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    seeds,
+                )?;
+                cranked_game = Some(game.key());
+            }
+            _ => {}
+        }
+    }
+
+    if ctx.accounts.global_state.require_can_start_game().is_ok() {
+        let game_bump = *ctx.bumps.get("next_game").unwrap();
+        let vault_bump = *ctx.bumps.get("next_game_vault").unwrap();
+        create_next_game(ctx.accounts, ctx.program_id, game_bump, vault_bump, seeds)?;
+        cranked_game = cranked_game.or(Some(ctx.accounts.next_game.key()));
+    }
+
+    let game_key = match cranked_game {
+        Some(game_key) => game_key,
+        None => {
+            require!(closed, QuickBetsErrors::NothingToCrank);
+            return Ok(());
+        }
+    };
+    pay_crank_reward(
+        &mut ctx.accounts.global_state,
+        game_key,
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.reward_receiver.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
+
+    Ok(())
+}
+
+/// Creates the game and vault PDAs for the next id of the series, same as start_game.
+fn create_next_game<'info>(
+    accounts: &mut CrankTick<'info>,
+    program_id: &Pubkey,
+    game_bump: u8,
+    vault_bump: u8,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let game_id_bytes = accounts.global_state.game_counter.to_le_bytes();
+    let game_seeds: &[&[&[u8]]] = &[&[GAME_SEED, game_id_bytes.as_slice(), &[game_bump]]];
+    create_pda_account(
+        accounts.signer.to_account_info(),
+        accounts.next_game.to_account_info(),
+        accounts.system_program.to_account_info(),
+        size_of::<Game>() + 12,
+        program_id,
+        game_seeds,
+    )?;
+
+    let next_game_key = accounts.next_game.key();
+    let vault_seeds: &[&[&[u8]]] = &[&[GAME_VAULT_SEED, next_game_key.as_ref(), &[vault_bump]]];
+    create_pda_account(
+        accounts.signer.to_account_info(),
+        accounts.next_game_vault.to_account_info(),
+        accounts.system_program.to_account_info(),
+        TokenAccount::LEN,
+        &token::ID,
+        vault_seeds,
+    )?;
+    token::initialize_account3(CpiContext::new(
+        accounts.token_program.to_account_info(),
+        token::InitializeAccount3 {
+            account: accounts.next_game_vault.to_account_info(),
+            mint: accounts.betting_token.to_account_info(),
+            authority: accounts.global_auth_pda.to_account_info(),
+        },
+    ))?;
+
+    let game_id = accounts.global_state.register_new_game(next_game_key);
+    let mut game = Box::<Game>::default();
+    game.start(
+        game_id,
+        accounts.next_game_vault.key(),
+        &accounts.global_state,
+    )?;
+    if game.market.payout_mode == PayoutMode::Parimutuel {
+        game.rollover_bonus = accounts.global_state.take_rollover_pool();
+    }
+    let house_seed = game.seed_outcomes();
+    accounts.global_state.stats.house_matched_volume += house_seed;
+    let mut data = accounts.next_game.try_borrow_mut_data()?;
+    game.try_serialize(&mut &mut data[..])?;
+
+    if game.rollover_bonus + house_seed > 0 {
+        transfer_tokens(
+            accounts.house_wallet.to_account_info(),
+            accounts.next_game_vault.to_account_info(),
+            accounts.global_auth_pda.to_account_info(),
+            accounts.token_program.to_account_info(),
+            game.rollover_bonus + house_seed,
+            Some(seeds),
+        )?;
+    }
+
+    emit!(GameStarted {
+        game: next_game_key,
+        game_vault: accounts.next_game_vault.key(),
+        betting_start: game.betting_start,
+        betting_time: accounts.global_state.betting_time,
+        rollover_bonus: game.rollover_bonus,
+        house_seed,
+    });
+    Ok(())
+}
+
+/// Closes a game that left the records once every bet is claimed, whatever dust is left in the
+/// vault goes to the house and the rent goes to the signer. Returns whether it closed anything.
+fn close_finished_game<'info>(accounts: &mut CrankTick<'info>, seeds: &[&[&[u8]]]) -> Result<bool> {
+    let (game, game_vault) = match (
+        accounts.game_to_close.as_mut(),
+        accounts.game_to_close_vault.as_ref(),
+    ) {
+        (Some(game), Some(game_vault)) => (game, game_vault),
+        _ => return Ok(false),
+    };
+    require_keys_eq!(
+        game.game_vault,
+        game_vault.key(),
+        QuickBetsErrors::GameVaultMismatch
+    );
+    if accounts.global_state.is_recorded(game.key()) || !game.check_all_bets_claimed() {
+        return Ok(false);
+    }
+
+    // a game cancelled through the emergency refund still holds its rollover bonus, it goes back
+    // to the rollover pool with the rest of the vault
+    if game.status == GameStatus::Cancelled {
+        accounts.global_state.rollover_pool += std::mem::take(&mut game.rollover_bonus);
+    }
+    game.transition(GameStatus::Closed, &accounts.global_state)?;
+    if accounts.global_state.to_close == game.key() {
+        accounts.global_state.to_close = Pubkey::default();
+    }
+
+    if game_vault.amount > 0 {
+        transfer_tokens(
+            game_vault.to_account_info(),
+            accounts.house_wallet.to_account_info(),
+            accounts.global_auth_pda.to_account_info(),
+            accounts.token_program.to_account_info(),
+            game_vault.amount,
+            Some(seeds),
+        )?;
+    }
+    token::close_account(CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        token::CloseAccount {
+            account: game_vault.to_account_info(),
+            destination: accounts.signer.to_account_info(),
+            authority: accounts.global_auth_pda.to_account_info(),
+        },
+        seeds,
+    ))?;
+    game.close(accounts.signer.to_account_info())?;
+
+    emit!(GameClosed {
+        game: game.key(),
+        game_vault: game_vault.key(),
+        closed_by: accounts.signer.key(),
+    });
+    Ok(true)
+}
+
+#[derive(Accounts)]
+pub struct CrankTick<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    // receives the crank reward
+    #[account(mut, constraint = reward_receiver.mint == house_wallet.mint)]
+    pub reward_receiver: Box<Account<'info, TokenAccount>>,
+
+    // a running game of the series, there's none before the first game
+    #[account(mut)]
+    pub game: Option<Box<Account<'info, Game>>>,
+    #[account(mut)]
+    pub game_vault: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// CHECK: the PDA of the next game, only created if a new game gets started
+    #[account(mut, seeds = [GAME_SEED, global_state.game_counter.to_le_bytes().as_slice()], bump)]
+    pub next_game: UncheckedAccount<'info>,
+    /// CHECK: the vault of the next game, created along with it
+    #[account(mut, seeds = [GAME_VAULT_SEED, next_game.key().as_ref()], bump)]
+    pub next_game_vault: UncheckedAccount<'info>,
+    #[account(constraint = house_wallet.mint == betting_token.key())]
+    pub betting_token: Box<Account<'info, Mint>>,
+
+    // a finished game that left the records, closed if every bet is claimed
+    #[account(mut)]
+    pub game_to_close: Option<Box<Account<'info, Game>>>,
+    #[account(mut)]
+    pub game_to_close_vault: Option<Box<Account<'info, TokenAccount>>>,
+
+    /// CHECK: Checking this manually in the instruction
+    pub sol_feed: AccountInfo<'info>,
+    /// CHECK: Checking this manually in the instruction
+    pub eth_feed: AccountInfo<'info>,
+    /// CHECK: the slot hashes sysvar, checked when reading it
+    pub slot_hashes: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn game_fits_in_a_stack_frame() {
+        // the default game can be built on the stack before it's boxed, a frame is 4KB and
+        // create_next_game needs room for the rest
+        assert!(size_of::<Game>() <= 3 * 1024);
+    }
+}
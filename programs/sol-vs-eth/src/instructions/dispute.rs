@@ -0,0 +1,217 @@
+use std::str::FromStr;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{
+    ETH_FEED_ID, ETH_ORACLE, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, SOL_FEED_ID, SOL_ORACLE,
+};
+use crate::events::ResolutionChallenged;
+use crate::instructions::finalize_settlement;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState, MarketType};
+use crate::utils::{get_price_update, pay_crank_reward, transfer_tokens};
+
+/// Challenges the final prices of a resolved game during its dispute window with verified pyth
+/// price updates of the sol and eth feeds, at least one of them. Every update published closer to
+/// the scheduled end, within the tolerance, replaces the final price. The challenger's bond is
+/// escrowed in the game vault, it's refunded if the challenge succeeds and goes to the house
+/// otherwise. A successful challenge restarts the dispute window so it can be countered.
+pub fn handle_challenge_resolution(ctx: Context<ChallengeResolution>) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &ctx.accounts.global_state;
+
+    game.require_status(GameStatus::Resolved)?;
+    require!(!game.dispute_over()?, QuickBetsErrors::DisputeWindowClosed);
+    // only the sol and eth observations can be challenged
+    require!(
+        game.market.market_type != MarketType::Race,
+        QuickBetsErrors::UnsupportedMarket
+    );
+
+    require_keys_eq!(
+        ctx.accounts.sol_feed.key(),
+        Pubkey::from_str(SOL_ORACLE).unwrap(),
+        QuickBetsErrors::InvalidOracle
+    );
+    require_keys_eq!(
+        ctx.accounts.eth_feed.key(),
+        Pubkey::from_str(ETH_ORACLE).unwrap(),
+        QuickBetsErrors::InvalidOracle
+    );
+    require!(
+        ctx.accounts.sol_price_update.is_some() || ctx.accounts.eth_price_update.is_some(),
+        QuickBetsErrors::NoPriceUpdate
+    );
+    let sol = challenged_price(
+        ctx.accounts.sol_price_update.as_ref(),
+        &ctx.accounts.sol_feed,
+        &SOL_FEED_ID,
+        game.scheduled_end,
+        global_state.publish_time_tolerance,
+    )?;
+    let eth = challenged_price(
+        ctx.accounts.eth_price_update.as_ref(),
+        &ctx.accounts.eth_feed,
+        &ETH_FEED_ID,
+        game.scheduled_end,
+        global_state.publish_time_tolerance,
+    )?;
+
+    let bond = global_state.challenge_bond;
+    if bond > 0 {
+        transfer_tokens(
+            ctx.accounts.payer.to_account_info(),
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.signer.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            bond,
+            None,
+        )?;
+    }
+
+    let successful = game.apply_challenge(sol, eth);
+    if successful {
+        game.dispute_end = Clock::get()?.unix_timestamp as u64 + game.dispute_window;
+    }
+
+    let mut bond_forfeited = 0;
+    if bond > 0 {
+        let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+        let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+        let bond_receiver = if successful {
+            ctx.accounts.payer.to_account_info()
+        } else {
+            bond_forfeited = bond;
+            ctx.accounts.house_wallet.to_account_info()
+        };
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            bond_receiver,
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            bond,
+            Some(seeds),
+        )?;
+    }
+
+    emit!(ResolutionChallenged {
+        game: game.key(),
+        challenger: ctx.accounts.signer.key(),
+        successful,
+        final_sol_price: game.final_sol_price,
+        final_eth_price: game.final_eth_price,
+        bond_forfeited,
+        dispute_end: game.dispute_end,
+    });
+    Ok(())
+}
+
+/// The price and publish time of a price update if one was passed, it has to be published within
+/// tolerance seconds of the scheduled end like the prices it challenges.
+fn challenged_price(
+    price_update: Option<&UncheckedAccount>,
+    push_feed: &AccountInfo,
+    feed_id: &[u8; 32],
+    scheduled_end: u64,
+    tolerance: u64,
+) -> Result<Option<(u64, i64)>> {
+    let price_update = match price_update {
+        Some(price_update) => price_update,
+        None => return Ok(None),
+    };
+    let (price, publish_time) = get_price_update(price_update, push_feed, feed_id)?;
+    msg!("Challenged price: {} published at {}", price, publish_time);
+    require!(
+        publish_time.abs_diff(scheduled_end as i64) <= tolerance,
+        QuickBetsErrors::NoPriceNearSettlement
+    );
+    Ok(Some((price, publish_time)))
+}
+
+/// Makes the settlement final once the dispute window is over, callable by anyone.
+pub fn handle_finalize_game(ctx: Context<FinalizeGame>) -> Result<()> {
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+
+    finalize_settlement(
+        &mut ctx.accounts.game,
+        &mut ctx.accounts.global_state,
+        &ctx.accounts.game_vault,
+        &ctx.accounts.house_wallet,
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
+
+    pay_crank_reward(
+        &mut ctx.accounts.global_state,
+        ctx.accounts.game.key(),
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.reward_receiver.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ChallengeResolution<'info> {
+    pub signer: Signer<'info>,
+    // the bond is escrowed from there and refunded to it if the challenge succeeds
+    #[account(mut, constraint = payer.mint == house_wallet.mint)]
+    pub payer: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    /// CHECK: Checking this manually in the instruction, only read for its exponent
+    pub sol_feed: AccountInfo<'info>,
+    /// CHECK: Checking this manually in the instruction, only read for its exponent
+    pub eth_feed: AccountInfo<'info>,
+    /// CHECK: a PriceUpdateV2 of the pyth receiver, checked when reading it
+    pub sol_price_update: Option<UncheckedAccount<'info>>,
+    /// CHECK: a PriceUpdateV2 of the pyth receiver, checked when reading it
+    pub eth_price_update: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeGame<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    // receives the crank reward
+    #[account(mut, constraint = reward_receiver.mint == house_wallet.mint)]
+    pub reward_receiver: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
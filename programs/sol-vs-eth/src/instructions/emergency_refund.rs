@@ -0,0 +1,77 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::BetClaimed;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState};
+use crate::utils::transfer_tokens;
+
+/// Lets any bettor get their stake back from a game that was never resolved.
+/// The first refund cancels the game so it can't be resolved afterwards.
+pub fn handle_emergency_refund(ctx: Context<EmergencyRefund>) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &mut ctx.accounts.global_state;
+
+    if game.status != GameStatus::Cancelled {
+        require!(
+            Clock::get()?.unix_timestamp as u64 > game.emergency_refund_time(),
+            QuickBetsErrors::RefundTooSoon
+        );
+        game.transition(GameStatus::Cancelled, global_state)?;
+        global_state.modify_game_record(game.key(), GameStatus::Cancelled);
+        global_state.release_top_up(game);
+    }
+
+    let user_bet = if let Some(user_bet) = game.get_user_bet(ctx.accounts.signer.key()) {
+        user_bet
+    } else {
+        return err!(QuickBetsErrors::NoBetFound);
+    };
+    require!(!user_bet.claimed, QuickBetsErrors::AlreadyClaimed);
+
+    game.mark_bet_claimed(ctx.accounts.signer.key())?;
+
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+    transfer_tokens(
+        ctx.accounts.game_vault.to_account_info(),
+        ctx.accounts.receiver.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        user_bet.amount,
+        Some(seeds),
+    )?;
+
+    emit!(BetClaimed {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        side: user_bet.side,
+        amount: user_bet.amount,
+        payout: user_bet.amount,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct EmergencyRefund<'info> {
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, token::authority = signer)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -3,7 +3,7 @@ use std::mem::size_of;
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Mint, Token, TokenAccount};
 
-use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, GLOBAL_STATE_SIZE};
 use crate::state::{GlobalAuth, GlobalState};
 
 pub fn handle_initialize(ctx: Context<Initialize>) -> Result<()> {
@@ -30,6 +30,9 @@ pub fn handle_initialize(ctx: Context<Initialize>) -> Result<()> {
 
     global_state.min_multiplier = 1.75;
 
+    // settlement prices have to be published within 5 seconds of the scheduled end
+    global_state.publish_time_tolerance = 5;
+
     Ok(())
 }
 
@@ -41,8 +44,7 @@ pub struct Initialize<'info> {
     #[account(init,
     seeds = [GLOBAL_STATE_SEED], bump,
     payer = signer,
-    // leaving some extra space for future upgrades
-    space = 400)]
+    space = GLOBAL_STATE_SIZE)]
     pub global_state: Box<Account<'info, GlobalState>>,
 
     #[account(init,
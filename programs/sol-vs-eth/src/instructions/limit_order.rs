@@ -0,0 +1,177 @@
+use std::mem::size_of;
+use std::str::FromStr;
+
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, LIMIT_ORDER_SEED};
+use crate::events::{LimitOrderPlaced, LimitOrderWithdrawn};
+use crate::instructions::charge_betting_fee;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{
+    Game, GameStatus, GlobalAuth, GlobalState, LimitOrder, LimitOrderStatus, PayoutMode,
+};
+use crate::utils::transfer_tokens;
+
+/// Escrows a bet that only joins the pool if the multiplier on its side is at least
+/// min_multiplier once the betting closes. The fee is taken now, orders that don't get filled can
+/// be withdrawn after the anticipation starts. The order gets its own account, so a user can only
+/// place one per game.
+pub fn handle_place_limit_order(
+    ctx: Context<PlaceLimitOrder>,
+    amount: u64,
+    side: u8,
+    min_multiplier: f64,
+) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let global_state = &mut ctx.accounts.global_state;
+
+    game.require_status(GameStatus::Betting)?;
+    require!(!game.commit_reveal, QuickBetsErrors::CommitRevealOnly);
+    require!(
+        game.betting_active(global_state.betting_time)?,
+        QuickBetsErrors::BettingInactive
+    );
+    // markets pooled by outcome and fixed odds aren't topped up, there's nothing for an order to
+    // wait for
+    require!(
+        !game.market.market_type.pooled_by_outcome()
+            && game.market.payout_mode != PayoutMode::FixedOdds,
+        QuickBetsErrors::UnsupportedMarket
+    );
+    require!(side <= 1, QuickBetsErrors::InvalidSide);
+    require!(
+        amount <= global_state.max_user_bet,
+        QuickBetsErrors::MaxUserBetExceeded
+    );
+
+    ctx.accounts.limit_order.set_inner(LimitOrder {
+        game: game.key(),
+        owner: ctx.accounts.signer.key(),
+        amount,
+        side,
+        min_multiplier,
+        status: LimitOrderStatus::Open,
+        placed_at: Clock::get()?.unix_timestamp as u64,
+    });
+    game.open_order_count += 1;
+    game.unfilled_orders_amount += amount;
+
+    transfer_tokens(
+        ctx.accounts.payer.to_account_info(),
+        ctx.accounts.game_vault.to_account_info(),
+        ctx.accounts.signer.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        amount,
+        None,
+    )?;
+    let fee = charge_betting_fee(
+        global_state,
+        amount,
+        ctx.accounts.payer.to_account_info(),
+        ctx.accounts.fees_wallet.to_account_info(),
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.signer.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+    )?;
+    global_state.stats.fees_collected += fee;
+
+    emit!(LimitOrderPlaced {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        side,
+        amount,
+        min_multiplier,
+        fee,
+    });
+    Ok(())
+}
+
+/// Returns the stake of an order that isn't in the pool, the fee isn't refunded. Filled orders
+/// only get their account closed.
+pub fn handle_withdraw_limit_order(ctx: Context<WithdrawLimitOrder>) -> Result<()> {
+    let game = &mut ctx.accounts.game;
+    let amount = game.withdraw_limit_order(&ctx.accounts.limit_order)?;
+
+    if amount > 0 {
+        let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+        let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+        transfer_tokens(
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.receiver.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            amount,
+            Some(seeds),
+        )?;
+    }
+
+    emit!(LimitOrderWithdrawn {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        amount,
+    });
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct PlaceLimitOrder<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(mut)]
+    pub payer: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(
+        init,
+        payer = signer,
+        space = size_of::<LimitOrder>() + 12,
+        seeds = [LIMIT_ORDER_SEED, game.key().as_ref(), signer.key().as_ref()],
+        bump
+    )]
+    pub limit_order: Box<Account<'info, LimitOrder>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, address = Pubkey::from_str("3kg54CAWcdq2gPCGjQ534brznAQpWHpJP1mpCA3DavZT").unwrap())]
+    pub fees_wallet: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Account<'info, GlobalState>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawLimitOrder<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    #[account(mut)]
+    pub game: Box<Account<'info, Game>>,
+
+    #[account(
+        mut,
+        seeds = [LIMIT_ORDER_SEED, game.key().as_ref(), signer.key().as_ref()],
+        bump,
+        close = signer
+    )]
+    pub limit_order: Box<Account<'info, LimitOrder>>,
+
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
+    pub game_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
+    pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
+
+    #[account(mut, token::authority = signer)]
+    pub receiver: Box<Account<'info, TokenAccount>>,
+
+    pub token_program: Program<'info, Token>,
+}
@@ -2,6 +2,7 @@ use anchor_lang::prelude::*;
 use anchor_lang::system_program;
 
 use crate::consts::REDEEMER_WALLET;
+use crate::events::AirdropClaimed;
 use crate::quick_bets_errors::QuickBetsErrors;
 
 pub fn handle_init_airdrop(ctx: Context<InitAirdrop>) -> Result<()> {
@@ -52,6 +53,13 @@ pub fn handle_claim_reward(ctx: Context<ClaimAirdrop>, discord_id: u64) -> Resul
     );
     system_program::transfer(cpi_context, airdrop.amount)?;
 
+    emit!(AirdropClaimed {
+        receiver: ctx.accounts.receiver.key(),
+        discord_id,
+        airdrop: airdrop.current_airdrop,
+        amount: airdrop.amount,
+    });
+
     Ok(())
 }
 
@@ -11,6 +11,14 @@ pub mod withdraw_funds;
 pub mod spin;
 pub mod shop;
 pub mod mlist_rewards;
+pub mod cancel_game;
+pub mod emergency_refund;
+pub mod crank_tick;
+pub mod limit_order;
+pub mod cancel_bet;
+pub mod cash_out;
+pub mod commit_reveal;
+pub mod dispute;
 
 
 pub use initialize::*;
@@ -25,4 +33,12 @@ pub use change_account_size::*;
 pub use withdraw_funds::*;
 pub use spin::*;
 pub use shop::*;
-pub use mlist_rewards::*;
\ No newline at end of file
+pub use mlist_rewards::*;
+pub use cancel_game::*;
+pub use emergency_refund::*;
+pub use crank_tick::*;
+pub use limit_order::*;
+pub use cancel_bet::*;
+pub use cash_out::*;
+pub use commit_reveal::*;
+pub use dispute::*;
\ No newline at end of file
@@ -5,32 +5,61 @@ use anchor_spl::token::{Token, TokenAccount};
 
 use crate::consts::USER_ACCOUNT_SEED;
 use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::BetPlaced;
 use crate::quick_bets_errors::QuickBetsErrors;
-use crate::state::{Game, GlobalAuth, GlobalState, User};
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState, PayoutMode, User};
 use crate::utils::transfer_tokens;
 
-pub fn handle_place_bet(ctx: Context<PlaceBet>, bet_size: u64, side: u8) -> Result<()> {
+/// expected_game, min_multiplier and max_pool_share protect the user from the odds moving
+/// between signing and execution. The multiplier and pool share are checked once the bet and
+/// the house match are in the pool, max_pool_share is the share of the pool on the chosen side,
+/// every 100 is 1%. On fixed odds min_multiplier is required and checked against the quote.
+pub fn handle_place_bet(
+    ctx: Context<PlaceBet>,
+    bet_size: u64,
+    side: u8,
+    expected_game: Pubkey,
+    min_multiplier: Option<f64>,
+    max_pool_share: Option<u64>,
+) -> Result<()> {
     let game = &mut ctx.accounts.game;
-    let global_state = &ctx.accounts.global_state;
+    let global_state = &mut ctx.accounts.global_state;
     let payer = &ctx.accounts.payer;
 
+    require_keys_eq!(game.key(), expected_game, QuickBetsErrors::WrongGame);
+    game.require_status(GameStatus::Betting)?;
+    require!(!game.commit_reveal, QuickBetsErrors::CommitRevealOnly);
     require!(
         game.betting_active(global_state.betting_time)?,
         QuickBetsErrors::BettingInactive
     );
 
+    if side >= game.outcome_count() {
+        msg!("Invalid side");
+        return Err(QuickBetsErrors::InvalidSide.into());
+    }
+    let fixed_odds = game.market.payout_mode == PayoutMode::FixedOdds;
     // check if there's any bet on the other side, if not, then match it upto the max_house_match.
-    let match_bet = match side {
-        0 => global_state.max_house_match > game.house_bet_amount,
-        1 => global_state.max_house_match > game.house_bet_amount,
-        _ => {
-            msg!("Invalid side");
-            return Err(QuickBetsErrors::InvalidSide.into());
-        }
-    };
-    let house_match_left = global_state.max_house_match - game.house_bet_amount;
-    if match_bet {
-        let matched_amount = std::cmp::min(bet_size, house_match_left);
+    // markets pooled by outcome aren't matched, the house seeded every outcome when they started
+    let match_bet = !game.market.market_type.pooled_by_outcome()
+        && global_state.max_house_match > game.house_bet_amount;
+    let mut matched_amount = 0;
+    let mut quote = 0.0;
+    if fixed_odds {
+        quote = game.quote_multiplier(side);
+        let min_multiplier = min_multiplier.ok_or(QuickBetsErrors::MinMultiplierRequired)?;
+        require!(quote >= min_multiplier, QuickBetsErrors::SlippageExceeded);
+        // the house takes the other side of the whole bet, up to max_house_bet_size
+        matched_amount = game.fixed_odds_cover(bet_size, quote);
+        require!(
+            matched_amount <= game.house_capacity_left(),
+            QuickBetsErrors::HouseExposureExceeded
+        );
+    } else if match_bet {
+        let house_match_left = global_state.max_house_match - game.house_bet_amount;
+        matched_amount = std::cmp::min(bet_size, house_match_left);
+    }
+    if matched_amount > 0 {
         if matched_amount > ctx.accounts.house_wallet.amount {
             msg!("Not enough funds to match bet");
             return Err(QuickBetsErrors::HouseBankrupt.into());
@@ -47,12 +76,8 @@ pub fn handle_place_bet(ctx: Context<PlaceBet>, bet_size: u64, side: u8) -> Resu
             Some(seeds),
         )?;
         game.house_bet_amount += matched_amount;
-        if side == 0 {
-            game.eth_bet_size += matched_amount;
-            // house is betting the opposite side
-        } else {
-            game.sol_bet_size += matched_amount;
-        }
+        // house is betting the opposite side
+        game.add_to_side(1 - side, matched_amount);
     }
 
     // transfer the user bet to the vault
@@ -65,34 +90,101 @@ pub fn handle_place_bet(ctx: Context<PlaceBet>, bet_size: u64, side: u8) -> Resu
         None,
     )?;
 
-    // transfer the fees to the house wallet
-    let fee = (bet_size as f64 * (global_state.betting_fees as f64 / 100.0 / 100.0)) as u64;
-    transfer_tokens(
+    let fee = charge_betting_fee(
+        global_state,
+        bet_size,
         ctx.accounts.payer.to_account_info(),
         ctx.accounts.fees_wallet.to_account_info(),
+        ctx.accounts.house_wallet.to_account_info(),
         ctx.accounts.signer.to_account_info(),
         ctx.accounts.token_program.to_account_info(),
-        fee,
-        None,
     )?;
 
     // update the game state
-    if side == 0 {
-        game.sol_bet_size += bet_size;
-    } else {
-        game.eth_bet_size += bet_size;
-    }
+    game.add_to_side(side, bet_size);
     msg!("user bet size : {}", bet_size);
-    let total_user_bet = game.add_user_bet(ctx.accounts.signer.key(), bet_size, side)?;
+    let total_user_bet = game.add_user_bet(
+        ctx.accounts.signer.key(),
+        bet_size,
+        side,
+        Clock::get()?.unix_timestamp as u64,
+        global_state.betting_time,
+    )?;
+    game.add_house_match(ctx.accounts.signer.key(), matched_amount)?;
+    if fixed_odds {
+        game.lock_quote(ctx.accounts.signer.key(), bet_size, quote)?;
+    }
     msg!("total user bet : {}", total_user_bet);
     ctx.accounts.user_account.add_volume(bet_size);
+    global_state.record_bet(&game.market.market_type, side, bet_size, fee, matched_amount);
     require!(
         total_user_bet <= global_state.max_user_bet,
         QuickBetsErrors::MaxUserBetExceeded
     );
+
+    // the house tops up the pool at the anticipation so winners get at least min_multiplier,
+    // refuse the bet if that's more than the house can still put in.
+    global_state.reserve_top_up(game, ctx.accounts.house_wallet.amount - matched_amount)?;
+
+    // the quote of a fixed odds bet was checked before it was placed
+    if let Some(min_multiplier) = min_multiplier.filter(|_| !fixed_odds) {
+        let user_bet = game
+            .get_user_bet(ctx.accounts.signer.key())
+            .ok_or(QuickBetsErrors::NoBetFound)?;
+        require!(
+            game.bet_multiplier(&user_bet) >= min_multiplier,
+            QuickBetsErrors::SlippageExceeded
+        );
+    }
+    if let Some(max_pool_share) = max_pool_share {
+        require!(
+            game.pool_share(side) <= max_pool_share,
+            QuickBetsErrors::SlippageExceeded
+        );
+    }
+
+    emit!(BetPlaced {
+        game: game.key(),
+        user: ctx.accounts.signer.key(),
+        side,
+        amount: bet_size,
+        total_user_bet,
+        fee,
+        house_matched: matched_amount,
+        sol_bet_size: game.sol_bet_size,
+        eth_bet_size: game.eth_bet_size,
+    });
     Ok(())
 }
 
+/// Takes the betting fee on a bet of that size from the payer, returns the fee.
+pub fn charge_betting_fee<'info>(
+    global_state: &mut GlobalState,
+    bet_size: u64,
+    payer: AccountInfo<'info>,
+    fees_wallet: AccountInfo<'info>,
+    house_wallet: AccountInfo<'info>,
+    signer: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+) -> Result<u64> {
+    let fee = (bet_size as f64 * (global_state.betting_fees as f64 / 100.0 / 100.0)) as u64;
+    // part of the fee is set aside in the house wallet to pay the crank rewards
+    let crank_fee = (fee as f64 * (global_state.crank_fee_share as f64 / 100.0 / 100.0)) as u64;
+    transfer_tokens(
+        payer.clone(),
+        fees_wallet,
+        signer.clone(),
+        token_program.clone(),
+        fee - crank_fee,
+        None,
+    )?;
+    if crank_fee > 0 {
+        transfer_tokens(payer, house_wallet, signer, token_program, crank_fee, None)?;
+        global_state.crank_reward_pool += crank_fee;
+    }
+    Ok(fee)
+}
+
 #[derive(Accounts)]
 pub struct PlaceBet<'info> {
     #[account(mut)]
@@ -108,7 +200,7 @@ pub struct PlaceBet<'info> {
     bump)]
     pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
 
-    #[account(mut)]
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
     pub game_vault: Box<Account<'info, TokenAccount>>,
 
     #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
@@ -119,7 +211,7 @@ pub struct PlaceBet<'info> {
     #[account(mut, seeds = [USER_ACCOUNT_SEED, signer.key.as_ref()], bump)]
     pub user_account: Account<'info, User>,
 
-    #[account(seeds = [GLOBAL_STATE_SEED], bump)]
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
     pub global_state: Account<'info, GlobalState>,
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
@@ -1,86 +1,38 @@
 use std::collections::HashMap;
-use std::str::FromStr;
 
 use anchor_lang::prelude::*;
 use anchor_lang::system_program;
 use anchor_spl::token;
 use anchor_spl::token::{Token, TokenAccount};
 
-use crate::consts::{ETH_ORACLE, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, MARGIN_OF_ERROR, SOL_ORACLE};
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, USER_ACCOUNT_SEED};
+use crate::events::{GameFinalized, GameResolved, WinningsPaid};
 use crate::quick_bets_errors::QuickBetsErrors;
-use crate::state::{Game, GameStatus, GlobalAuth, GlobalState, User};
-use crate::utils::{get_price_from_pyth, transfer_tokens};
+use crate::state::{Game, GameStatus, GlobalAuth, GlobalState, MarketType, User};
+use crate::utils::{
+    get_jitter, get_race_settlement_prices, get_settlement_prices, pay_crank_reward,
+    transfer_tokens, SettlementPrices,
+};
 
-pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
-    let game = &mut ctx.accounts.game;
-    let global_state = &mut ctx.accounts.global_state;
-
-    global_state.confirm_crank_admin(&ctx.accounts.signer)?;
-
-    require!(!game.is_settled, QuickBetsErrors::BetAlreadySettled);
-
-    if game.anticipating_start + global_state.anticipation_time
-        > Clock::get()?.unix_timestamp as u64 + MARGIN_OF_ERROR
-    {
-        return Err(QuickBetsErrors::AnticipationTimeTooSoon.into());
-    }
-
-    if Pubkey::from_str(SOL_ORACLE).unwrap() != *ctx.accounts.sol_feed.key {
-        return Err(QuickBetsErrors::InvalidOracle.into());
-    }
-
-    if Pubkey::from_str(ETH_ORACLE).unwrap() != *ctx.accounts.eth_feed.key {
-        return Err(QuickBetsErrors::InvalidOracle.into());
-    }
-
-    let mut sol_price = get_price_from_pyth(ctx.accounts.sol_feed.clone())?;
-    let mut eth_price = get_price_from_pyth(ctx.accounts.eth_feed.clone())?;
-    if game.anticipating_start + global_state.anticipation_time + 15
-        < Clock::get()?.unix_timestamp as u64
-    {
-        sol_price = game.initial_sol_price;
-        eth_price = game.initial_eth_price;
-    }
-
-    msg!("anticipation start : {}", game.anticipating_start);
-    msg!("End time: {} ", Clock::get()?.unix_timestamp);
-    msg!("Sol price: {}", sol_price);
-    msg!("Eth price: {}", eth_price);
-    game.final_sol_price = sol_price;
-    game.final_eth_price = eth_price;
-    game.is_settled = true;
-    game.anticipating_end = Clock::get()?.unix_timestamp as u64;
-
-    ctx.accounts
-        .global_state
-        .modify_game_record(game.key(), GameStatus::Resolved);
-
-    let pool_size = game.sol_bet_size + game.eth_bet_size;
-    let winners_multiplier = if game.get_winner() == 0 {
-        pool_size as f64 / game.sol_bet_size as f64
-    } else if game.get_winner() == 1 {
-        pool_size as f64 / game.eth_bet_size as f64
-    } else {
-        0.0
-    };
-
-    let amount_owed_to_winners = game.get_amount_owed_to_winners(winners_multiplier);
-    msg!("Amount owed to winners: {}", amount_owed_to_winners);
-    msg!("Amount in game vault: {}", ctx.accounts.game_vault.amount);
-    let won_by_house = ctx.accounts.game_vault.amount - amount_owed_to_winners;
+/// On a race the remaining accounts are its feeds, in the order of the outcomes.
+pub fn handle_resolve_game<'info>(ctx: Context<'_, '_, '_, 'info, ResolveBet<'info>>) -> Result<()> {
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
 
-    if won_by_house > 0 {
-        let bump = *ctx.bumps.get("global_auth_pda").unwrap();
-        let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
-        transfer_tokens(
-            ctx.accounts.game_vault.to_account_info(),
-            ctx.accounts.house_wallet.to_account_info(),
-            ctx.accounts.global_auth_pda.to_account_info(),
-            ctx.accounts.token_program.to_account_info(),
-            won_by_house,
-            Some(seeds),
-        )?;
-    }
+    settle_game(
+        &mut ctx.accounts.game,
+        &mut ctx.accounts.global_state,
+        &ctx.accounts.sol_feed,
+        &ctx.accounts.eth_feed,
+        ctx.remaining_accounts,
+        &ctx.accounts.slot_hashes,
+        &ctx.accounts.game_vault,
+        &ctx.accounts.house_wallet,
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
+    let game = &mut ctx.accounts.game;
 
     let account_infos = [
         ctx.accounts.player_account_1.to_account_info(),
@@ -96,6 +48,10 @@ pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
     let mut amount_to_pay: HashMap<Pubkey, u64> = HashMap::new();
 
     for (i, account) in account_infos.iter().enumerate() {
+        // with a dispute window the winners claim once it's over
+        if !game.finalized {
+            break;
+        }
         if account.key == ctx.accounts.signer.key {
             continue;
         }
@@ -109,50 +65,53 @@ pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
             continue;
         }
 
-        msg!(
-            "adding user record, amount: {}, side: {}",
-            user_bet.amount,
-            user_bet.side == game.get_winner()
-        );
-        match i {
-            0 => {
-                ctx.accounts
-                    .player_user_account_1
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
-            }
-            1 => {
-                ctx.accounts
-                    .player_user_account_2
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
-            }
-            2 => {
-                ctx.accounts
-                    .player_user_account_3
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
-            }
-            3 => {
-                ctx.accounts
-                    .player_user_account_4
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
-            }
-            4 => {
-                ctx.accounts
-                    .player_user_account_5
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+        // a voided game refunds every bet and doesn't count towards the streaks
+        if game.status != GameStatus::Voided {
+            msg!(
+                "adding user record, amount: {}, side: {}",
+                user_bet.amount,
+                user_bet.side == game.get_winner()
+            );
+            match i {
+                0 => {
+                    ctx.accounts
+                        .player_user_account_1
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                1 => {
+                    ctx.accounts
+                        .player_user_account_2
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                2 => {
+                    ctx.accounts
+                        .player_user_account_3
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                3 => {
+                    ctx.accounts
+                        .player_user_account_4
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                4 => {
+                    ctx.accounts
+                        .player_user_account_5
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                5 => {
+                    ctx.accounts
+                        .player_user_account_6
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                6 => {
+                    ctx.accounts
+                        .player_user_account_7
+                        .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
+                }
+                _ => {}
             }
-            5 => {
-                ctx.accounts
-                    .player_user_account_6
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
-            }
-            6 => {
-                ctx.accounts
-                    .player_user_account_7
-                    .add_bet_record(user_bet.amount, user_bet.side == game.get_winner());
-            }
-            _ => {}
         }
-        let payout_amount = game.calculate_winning_amount(user_bet.amount, user_bet.side);
+        let payout_amount = game.calculate_winning_amount(&user_bet);
         game.mark_bet_claimed(*account.key)?;
         total_payouts += payout_amount;
         if payout_amount > 0 {
@@ -160,6 +119,17 @@ pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
         }
     }
 
+    // the crank reward lands in the signer's wsol account and gets unwrapped with the payouts
+    pay_crank_reward(
+        &mut ctx.accounts.global_state,
+        ctx.accounts.game.key(),
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.signer_wsol_account.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
+
     if total_payouts == 0 {
         let cpi_accounts = token::CloseAccount {
             account: ctx.accounts.signer_wsol_account.to_account_info(),
@@ -173,8 +143,6 @@ pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
         token::close_account(cpi_ctx)?;
         return Ok(());
     }
-    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
-    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
     transfer_tokens(
         ctx.accounts.game_vault.to_account_info(),
         ctx.accounts.signer_wsol_account.to_account_info(),
@@ -195,6 +163,7 @@ pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
 
     token::close_account(cpi_ctx)?;
 
+    let game_key = ctx.accounts.game.key();
     for (key, value) in amount_to_pay {
         // get key account from the accounts array
         let mut user = ctx.accounts.signer_wsol_account.to_account_info();
@@ -211,18 +180,166 @@ pub fn handle_resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
             },
         );
         system_program::transfer(cpi_context, value)?;
+
+        emit!(WinningsPaid {
+            game: game_key,
+            user: key,
+            amount: value,
+        });
     }
 
     Ok(())
 }
 
+/// Resolves the game, and finalizes it right away unless the resolution can be disputed,
+/// shared by resolve_game and crank_tick. Anyone can call them, the prices are bound to the
+/// scheduled end of the game.
+#[allow(clippy::too_many_arguments)]
+pub fn settle_game<'info>(
+    game: &mut Account<'info, Game>,
+    global_state: &mut Account<'info, GlobalState>,
+    sol_feed: &AccountInfo<'info>,
+    eth_feed: &AccountInfo<'info>,
+    race_feeds: &[AccountInfo<'info>],
+    slot_hashes: &AccountInfo<'info>,
+    game_vault: &Account<'info, TokenAccount>,
+    house_wallet: &Account<'info, TokenAccount>,
+    global_auth_pda: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // the jitter from the slot committed at the anticipation start moves the scheduled end
+    let jitter = get_jitter(slot_hashes, game.settlement_jitter, game.jitter_slot)?;
+    game.scheduled_end += jitter;
+
+    // a game resolved too late is voided with its initial prices, the oracles aren't read
+    let voided = if game.resolution_expired()? {
+        let (sol_price, eth_price) = (game.initial_sol_price, game.initial_eth_price);
+        let race_prices = game.race_initial_prices;
+        game.resolve(global_state, sol_price, eth_price, race_prices)?
+    } else {
+        // a race only reads its own feeds
+        let prices = if game.market.market_type == MarketType::Race {
+            SettlementPrices::default()
+        } else {
+            get_settlement_prices(
+                sol_feed,
+                eth_feed,
+                game.scheduled_end,
+                global_state.publish_time_tolerance,
+            )?
+        };
+        let race_prices = get_race_settlement_prices(
+            race_feeds,
+            &game.market,
+            game.scheduled_end,
+            global_state.publish_time_tolerance,
+        )?;
+        game.sol_publish_time = prices.sol_publish_time;
+        game.eth_publish_time = prices.eth_publish_time;
+        game.resolve(global_state, prices.sol_price, prices.eth_price, race_prices)?
+    };
+
+    msg!("anticipation start : {}", game.anticipating_start);
+    msg!("End time: {} ", game.anticipating_end);
+    global_state.modify_game_record(game.key(), game.status.clone());
+
+    emit!(GameResolved {
+        game: game.key(),
+        anticipating_end: game.anticipating_end,
+        final_sol_price: game.final_sol_price,
+        final_eth_price: game.final_eth_price,
+        winner: game.get_winner(),
+        voided,
+        scheduled_end: game.scheduled_end,
+        dispute_end: game.dispute_end,
+    });
+
+    if !game.dispute_over()? {
+        msg!("payouts are held until {}", game.dispute_end);
+        return Ok(());
+    }
+    finalize_settlement(
+        game,
+        global_state,
+        game_vault,
+        house_wallet,
+        global_auth_pda,
+        token_program,
+        seeds,
+    )
+}
+
+/// Once the resolution can't be disputed anymore, sends what the house won back to the house
+/// wallet and opens the claims.
+pub fn finalize_settlement<'info>(
+    game: &mut Account<'info, Game>,
+    global_state: &mut Account<'info, GlobalState>,
+    game_vault: &Account<'info, TokenAccount>,
+    house_wallet: &Account<'info, TokenAccount>,
+    global_auth_pda: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    require!(
+        matches!(game.status, GameStatus::Resolved | GameStatus::Voided) && !game.finalized,
+        QuickBetsErrors::InvalidGameStatus
+    );
+    require!(game.dispute_over()?, QuickBetsErrors::SettlementNotFinal);
+    game.finalized = true;
+
+    let amount_owed_to_winners = game.amount_owed_to_users();
+    msg!("Amount owed to winners: {}", amount_owed_to_winners);
+    msg!("Amount in game vault: {}", game_vault.amount);
+    let won_by_house = game_vault.amount - amount_owed_to_winners;
+    // the house gets its stake back from a pool that rolls over, the rest goes to the next game.
+    // A voided game keeps the bonus it got for the next one.
+    let rolled_over = if game.pool_rolls_over() {
+        won_by_house.saturating_sub(game.house_bet_amount)
+    } else if game.status == GameStatus::Voided {
+        game.rollover_bonus
+    } else {
+        0
+    };
+    global_state.rollover_pool += rolled_over;
+    global_state.record_resolution(
+        game.is_draw(),
+        game.status == GameStatus::Voided,
+        won_by_house - rolled_over,
+        game.house_bet_amount,
+    );
+    emit!(GameFinalized {
+        game: game.key(),
+        final_sol_price: game.final_sol_price,
+        final_eth_price: game.final_eth_price,
+        winner: game.get_winner(),
+        amount_owed_to_winners,
+        won_by_house: won_by_house - rolled_over,
+        rolled_over,
+    });
+
+    // the rolled over pool waits in the house wallet for the next game
+    if won_by_house > 0 {
+        transfer_tokens(
+            game_vault.to_account_info(),
+            house_wallet.to_account_info(),
+            global_auth_pda,
+            token_program,
+            won_by_house,
+            Some(seeds),
+        )?;
+    }
+    Ok(())
+}
+
 #[derive(Accounts)]
 pub struct ResolveBet<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
     #[account(mut)]
     pub game: Box<Account<'info, Game>>,
-    #[account(mut)]
+    // also receives the crank reward
+    #[account(mut, token::authority = signer)]
     pub signer_wsol_account: Box<Account<'info, TokenAccount>>,
     #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
     pub global_state: Account<'info, GlobalState>,
@@ -230,6 +347,8 @@ pub struct ResolveBet<'info> {
     pub sol_feed: AccountInfo<'info>,
     /// CHECK: Checking this manually in the instruction
     pub eth_feed: AccountInfo<'info>,
+    /// CHECK: the slot hashes sysvar, checked when reading it
+    pub slot_hashes: AccountInfo<'info>,
     #[account(mut, constraint = game.game_vault == game_vault.key())]
     pub game_vault: Account<'info, TokenAccount>,
 
@@ -246,37 +365,37 @@ pub struct ResolveBet<'info> {
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_1: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_1.key().as_ref()], bump)]
     pub player_user_account_1: Box<Account<'info, User>>,
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_2: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_2.key().as_ref()], bump)]
     pub player_user_account_2: Box<Account<'info, User>>,
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_3: AccountInfo<'info>,
 
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_3.key().as_ref()], bump)]
     pub player_user_account_3: Box<Account<'info, User>>,
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_4: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_4.key().as_ref()], bump)]
     pub player_user_account_4: Box<Account<'info, User>>,
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_5: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_5.key().as_ref()], bump)]
     pub player_user_account_5: Box<Account<'info, User>>,
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_6: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_6.key().as_ref()], bump)]
     pub player_user_account_6: Box<Account<'info, User>>,
     /// CHECK: will be checked manually in the instruction
     #[account(mut)]
     pub player_account_7: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(mut, seeds = [USER_ACCOUNT_SEED, player_account_7.key().as_ref()], bump)]
     pub player_user_account_7: Box<Account<'info, User>>,
 }
@@ -1,6 +1,7 @@
 use anchor_lang::prelude::*;
 
 use crate::consts::{ADMIN_WALLETS, USER_ACCOUNT_SEED};
+use crate::events::ShopPurchase;
 use crate::quick_bets_errors::QuickBetsErrors;
 use crate::state::User;
 
@@ -35,6 +36,14 @@ pub fn handle_buy_item(
         item_id,
         item.price
     );
+    emit!(ShopPurchase {
+        buyer: ctx.accounts.buyer.key(),
+        discord_id,
+        item_id,
+        edition,
+        price: item.price,
+        quantity_left: item.quantity_left,
+    });
     Ok(())
 }
 
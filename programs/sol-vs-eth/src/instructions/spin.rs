@@ -3,6 +3,7 @@ use std::str::FromStr;
 use anchor_lang::prelude::*;
 
 use crate::consts::USER_ACCOUNT_SEED;
+use crate::events::SpinRegistered;
 use crate::quick_bets_errors::QuickBetsErrors;
 use crate::state::User;
 
@@ -20,6 +21,12 @@ pub fn handle_use_spin(ctx: Context<UseSpin>, result: u16) -> Result<()> {
     ctx.accounts.user_spin_account.check_spin_eligible()?;
     ctx.accounts.user_spin_account.register_spin(result);
 
+    emit!(SpinRegistered {
+        user: ctx.accounts.user.key(),
+        reward: result,
+        total_reward: ctx.accounts.user_spin_account.reward,
+        spin_time: ctx.accounts.user_spin_account.last_spin,
+    });
 
     Ok(())
 }
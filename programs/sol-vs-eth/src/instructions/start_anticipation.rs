@@ -1,80 +1,160 @@
-use std::str::FromStr;
-
 use anchor_lang::prelude::*;
 use anchor_spl::token::{Token, TokenAccount};
 
-use crate::consts::{ETH_ORACLE, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED, MARGIN_OF_ERROR, SOL_ORACLE};
+use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::{AnticipationStarted, LimitOrderSettled};
 use crate::quick_bets_errors::QuickBetsErrors;
-use crate::state::{Game, GameStatus, GlobalAuth, GlobalState};
-use crate::utils::{get_price_from_pyth, transfer_tokens};
+use crate::state::{
+    Game, GameStatus, GlobalAuth, GlobalState, LimitOrder, LimitOrderStatus, MarketType,
+};
+use crate::utils::{get_game_prices, get_race_prices, pay_crank_reward, transfer_tokens};
+
+/// On a race the remaining accounts are its feeds, in the order of the outcomes. They're followed
+/// by every open limit order of the game.
+pub fn handle_start_anticipation<'info>(
+    ctx: Context<'_, '_, '_, 'info, StartAnticipation<'info>>,
+) -> Result<()> {
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+
+    anticipate_game(
+        &mut ctx.accounts.game,
+        &mut ctx.accounts.global_state,
+        &ctx.accounts.sol_feed,
+        &ctx.accounts.eth_feed,
+        ctx.remaining_accounts,
+        &ctx.accounts.game_vault,
+        &ctx.accounts.house_wallet,
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
 
-pub fn handle_start_anticipation(ctx: Context<StartAnticipation>) -> Result<()> {
-    let game = &mut ctx.accounts.game;
-    let global_state = &mut ctx.accounts.global_state;
+    pay_crank_reward(
+        &mut ctx.accounts.global_state,
+        ctx.accounts.game.key(),
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.reward_receiver.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
 
-    global_state.confirm_crank_admin(&ctx.accounts.signer)?;
+    Ok(())
+}
 
+/// Moves the game to anticipation, settles the limit orders and moves the house top up to the
+/// vault, shared by start_anticipation and crank_tick. The remaining accounts are the feeds of a
+/// race followed by the open limit orders.
+#[allow(clippy::too_many_arguments)]
+pub fn anticipate_game<'info>(
+    game: &mut Account<'info, Game>,
+    global_state: &mut Account<'info, GlobalState>,
+    sol_feed: &AccountInfo<'info>,
+    eth_feed: &AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    game_vault: &Account<'info, TokenAccount>,
+    house_wallet: &Account<'info, TokenAccount>,
+    global_auth_pda: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    // with no tolerance only a price published at the exact scheduled end could settle the game
+    require!(
+        global_state.publish_time_tolerance > 0,
+        QuickBetsErrors::PublishTimeToleranceUnset
+    );
     msg!("anticipation start : {}", game.betting_start);
     msg!("current time: {} ", Clock::get()?.unix_timestamp);
-    // the + 1 at the end is the game allowing a margin of error of 1 second,
-    // most games should still end at the exact time
-
-    if game.betting_start + global_state.betting_time > Clock::get()?.unix_timestamp as u64 + MARGIN_OF_ERROR {
-        return Err(QuickBetsErrors::BettingTimeTooSoon.into());
-    }
-
-    if Pubkey::from_str(SOL_ORACLE).unwrap() != *ctx.accounts.sol_feed.key {
-        return Err(QuickBetsErrors::InvalidOracle.into());
-    }
 
-    if Pubkey::from_str(ETH_ORACLE).unwrap() != *ctx.accounts.eth_feed.key {
-        return Err(QuickBetsErrors::InvalidOracle.into());
-    }
+    // a race only reads its own feeds
+    let (sol_price, eth_price) = if game.market.market_type == MarketType::Race {
+        (0, 0)
+    } else {
+        get_game_prices(sol_feed, eth_feed)?
+    };
+    let race_prices = get_race_prices(remaining_accounts, &game.market)?;
+
+    let feed_count = if game.market.market_type == MarketType::Race {
+        game.market.race_feed_count as usize
+    } else {
+        0
+    };
+    let mut orders = remaining_accounts
+        .get(feed_count..)
+        .unwrap_or_default()
+        .iter()
+        .map(Account::<LimitOrder>::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    // every open order has to be settled, nobody can leave one out to change the odds
+    let mut order_keys: Vec<Pubkey> = orders.iter().map(|order| order.key()).collect();
+    order_keys.sort();
+    order_keys.dedup();
+    require!(
+        order_keys.len() == orders.len()
+            && orders.len() as u64 == game.open_order_count
+            && orders.iter().all(|order| {
+                order.game == game.key() && order.status == LimitOrderStatus::Open
+            }),
+        QuickBetsErrors::LimitOrdersMissing
+    );
+    let mut order_states: Vec<LimitOrder> = orders.iter().map(|order| (**order).clone()).collect();
+
+    // the reservation of this game is what it can top up with, the limit orders only get what
+    // isn't reserved for the other games
+    let house_available = global_state.house_available_for(game, house_wallet.amount);
+    global_state.release_top_up(game);
+    let matched_amount = game.start_anticipation(
+        global_state,
+        sol_price,
+        eth_price,
+        race_prices,
+        &mut order_states,
+        house_available,
+    )?;
 
-    let sol_price = get_price_from_pyth(ctx.accounts.sol_feed.clone())?;
-    let eth_price = get_price_from_pyth(ctx.accounts.eth_feed.clone())?;
-    msg!("Sol price: {}", sol_price);
-    msg!("Eth price: {}", eth_price);
-    game.initial_sol_price = sol_price;
-    game.initial_eth_price = eth_price;
-
-    game.anticipating_start = Clock::get()?.unix_timestamp as u64;
-    let min_for_sol_payout = game.sol_bet_size as f64 * global_state.min_multiplier;
-    let min_for_eth_payout = game.eth_bet_size as f64 * global_state.min_multiplier;
-    let pool_size = game.sol_bet_size + game.eth_bet_size;
-
-    let mut matched_amount = 0_f64;
-    if min_for_sol_payout > pool_size as f64 {
-        matched_amount = min_for_sol_payout - pool_size as f64;
-        game.eth_bet_size += matched_amount as u64;
-    }
-    if min_for_eth_payout > pool_size as f64 {
-        matched_amount = min_for_eth_payout - pool_size as f64;
-        game.sol_bet_size += matched_amount as u64;
+    global_state.modify_game_record(game.key(), GameStatus::Anticipation);
+    global_state.stats.house_matched_volume += matched_amount;
+
+    // the fees of the limit orders were counted when they were placed
+    for (order, state) in orders.iter_mut().zip(order_states) {
+        order.set_inner(state);
+        order.exit(&crate::ID)?;
+        let filled = order.status == LimitOrderStatus::Filled;
+        if filled {
+            global_state.record_bet(&game.market.market_type, order.side, order.amount, 0, 0);
+        }
+        emit!(LimitOrderSettled {
+            game: game.key(),
+            user: order.owner,
+            side: order.side,
+            amount: order.amount,
+            filled,
+        });
     }
 
-    matched_amount = std::cmp::min(
-        matched_amount as u64,
-        global_state.max_house_bet_size - game.house_bet_amount,
-    ) as f64;
-
-    ctx.accounts
-        .global_state
-        .modify_game_record(game.key(), GameStatus::Anticipation);
-
-    if matched_amount == 0_f64 {
+    emit!(AnticipationStarted {
+        game: game.key(),
+        anticipating_start: game.anticipating_start,
+        initial_sol_price: game.initial_sol_price,
+        initial_eth_price: game.initial_eth_price,
+        sol_bet_size: game.sol_bet_size,
+        eth_bet_size: game.eth_bet_size,
+        house_top_up: matched_amount,
+        scheduled_end: game.scheduled_end,
+    });
+
+    if matched_amount == 0 {
         msg!("odds are good enough already, no need to match");
         return Ok(());
     }
 
-    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
-    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
     transfer_tokens(
-        ctx.accounts.house_wallet.to_account_info(),
-        ctx.accounts.game_vault.to_account_info(),
-        ctx.accounts.global_auth_pda.to_account_info(),
-        ctx.accounts.token_program.to_account_info(),
-        matched_amount as u64,
+        house_wallet.to_account_info(),
+        game_vault.to_account_info(),
+        global_auth_pda,
+        token_program,
+        matched_amount,
         Some(seeds),
     )?;
 
@@ -93,8 +173,11 @@ pub struct StartAnticipation<'info> {
     #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
     pub house_wallet: Box<Account<'info, TokenAccount>>,
 
-    #[account(mut)]
+    #[account(mut, constraint = game.game_vault == game_vault.key())]
     pub game_vault: Box<Account<'info, TokenAccount>>,
+    // receives the crank reward
+    #[account(mut, constraint = reward_receiver.mint == house_wallet.mint)]
+    pub reward_receiver: Box<Account<'info, TokenAccount>>,
     /// CHECK: Checking this manually in the instruction
     pub sol_feed: AccountInfo<'info>,
     /// CHECK: Checking this manually in the instruction
@@ -4,28 +4,66 @@ use anchor_lang::prelude::*;
 use anchor_spl::token::TokenAccount;
 use anchor_spl::token::{Mint, Token};
 
-use crate::consts::{GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
-use crate::quick_bets_errors::QuickBetsErrors;
-use crate::state::{Game, GameStatus, GlobalAuth, GlobalState};
+use crate::consts::{GAME_SEED, GAME_VAULT_SEED, GLOBAL_AUTH_SEED, GLOBAL_STATE_SEED};
+use crate::events::GameStarted;
+use crate::state::{Game, GlobalAuth, GlobalState, PayoutMode};
+use crate::utils::{pay_crank_reward, transfer_tokens};
 
 pub fn handle_start_game(ctx: Context<StartGame>) -> Result<()> {
-    ctx.accounts
+    ctx.accounts.global_state.require_can_start_game()?;
+
+    let game_id = ctx
+        .accounts
         .global_state
-        .confirm_crank_admin(&ctx.accounts.signer)?;
-    for game in ctx.accounts.global_state.game_records.iter() {
-        if game.status != GameStatus::Resolved && game.game_address != Pubkey::default() {
-            return Err(QuickBetsErrors::GameInProgress.into());
-        }
+        .register_new_game(ctx.accounts.game.key());
+    ctx.accounts.game.start(
+        game_id,
+        ctx.accounts.game_vault.key(),
+        &ctx.accounts.global_state,
+    )?;
+
+    let bump = *ctx.bumps.get("global_auth_pda").unwrap();
+    let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
+
+    // the pools of the draws before go to the winners of this game, fixed odds leave them to the
+    // next parimutuel game
+    let rollover_bonus = if ctx.accounts.game.market.payout_mode == PayoutMode::Parimutuel {
+        ctx.accounts.global_state.take_rollover_pool()
+    } else {
+        0
+    };
+    ctx.accounts.game.rollover_bonus = rollover_bonus;
+    // and the house seeds every outcome of a race or bucket market
+    let house_seed = ctx.accounts.game.seed_outcomes();
+    ctx.accounts.global_state.stats.house_matched_volume += house_seed;
+    if rollover_bonus + house_seed > 0 {
+        transfer_tokens(
+            ctx.accounts.house_wallet.to_account_info(),
+            ctx.accounts.game_vault.to_account_info(),
+            ctx.accounts.global_auth_pda.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            rollover_bonus + house_seed,
+            Some(seeds),
+        )?;
     }
-    let game = &mut ctx.accounts.game;
-    game.betting_start = Clock::get()?.unix_timestamp as u64;
-    game.eth_bet_size = 0;
-    game.sol_bet_size = 0;
-    game.game_vault = ctx.accounts.game_vault.key();
 
-    ctx.accounts
-        .global_state
-        .add_game_record(ctx.accounts.game.key());
+    emit!(GameStarted {
+        game: ctx.accounts.game.key(),
+        game_vault: ctx.accounts.game_vault.key(),
+        betting_start: ctx.accounts.game.betting_start,
+        betting_time: ctx.accounts.global_state.betting_time,
+        rollover_bonus,
+        house_seed,
+    });
+    pay_crank_reward(
+        &mut ctx.accounts.global_state,
+        ctx.accounts.game.key(),
+        ctx.accounts.house_wallet.to_account_info(),
+        ctx.accounts.reward_receiver.to_account_info(),
+        ctx.accounts.global_auth_pda.to_account_info(),
+        ctx.accounts.token_program.to_account_info(),
+        seeds,
+    )?;
 
     Ok(())
 }
@@ -35,20 +73,34 @@ pub struct StartGame<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
 
-    #[account(init, payer = signer, space = size_of::< Game > () + 12)]
+    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
+    pub global_state: Box<Account<'info, GlobalState>>,
+
+    #[account(init,
+    seeds = [GAME_SEED, global_state.game_counter.to_le_bytes().as_slice()], bump,
+    payer = signer,
+    space = size_of::< Game > () + 12)]
     pub game: Box<Account<'info, Game>>,
 
     #[account(mut, seeds = [GLOBAL_AUTH_SEED], bump)]
     pub global_auth_pda: Box<Account<'info, GlobalAuth>>,
 
-    #[account(mut, seeds = [GLOBAL_STATE_SEED], bump)]
-    pub global_state: Box<Account<'info, GlobalState>>,
-
     pub betting_token: Box<Account<'info, Mint>>,
 
-    #[account(init, payer = signer, token::mint = betting_token, token::authority = global_auth_pda)]
+    #[account(init,
+    seeds = [GAME_VAULT_SEED, game.key().as_ref()], bump,
+    payer = signer,
+    token::mint = betting_token,
+    token::authority = global_auth_pda)]
     pub game_vault: Box<Account<'info, TokenAccount>>,
 
+    #[account(mut, constraint = global_state.house_wallet == house_wallet.key())]
+    pub house_wallet: Box<Account<'info, TokenAccount>>,
+
+    // receives the crank reward
+    #[account(mut, constraint = reward_receiver.mint == house_wallet.mint)]
+    pub reward_receiver: Box<Account<'info, TokenAccount>>,
+
     pub system_program: Program<'info, System>,
     pub token_program: Program<'info, Token>,
 }
@@ -16,6 +16,13 @@ pub fn handle_withdraw_funds(ctx : Context<WithdrawFunds>, amount : u64) -> Resu
     };
 
 
+    // the top ups reserved for the running games stay in the wallet
+    let reserved_top_ups = ctx.accounts.global_state.reserved_top_ups;
+    require!(
+        amount <= ctx.accounts.house_wallet.amount.saturating_sub(reserved_top_ups),
+        QuickBetsErrors::HouseBankrupt
+    );
+
     let bump = *ctx.bumps.get("global_auth_pda").unwrap();
     let seeds: &[&[&[u8]]] = &[&[GLOBAL_AUTH_SEED, &[bump]]];
     transfer_tokens(
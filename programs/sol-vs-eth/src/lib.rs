@@ -1,9 +1,10 @@
 use anchor_lang::prelude::*;
 
 use crate::instructions::*;
-use crate::state::GlobalState;
+use crate::state::{GlobalState, MarketConfig};
 
 mod consts;
+mod events;
 mod instructions;
 mod quick_bets_errors;
 mod state;
@@ -23,18 +24,79 @@ mod mulberry_quick_bets {
         handle_start_game(ctx)
     }
 
-    pub fn place_bet(ctx: Context<PlaceBet>, bet_size: u64, side: u8) -> Result<()> {
-        handle_place_bet(ctx, bet_size, side)
+    pub fn place_bet(
+        ctx: Context<PlaceBet>,
+        bet_size: u64,
+        side: u8,
+        expected_game: Pubkey,
+        min_multiplier: Option<f64>,
+        max_pool_share: Option<u64>,
+    ) -> Result<()> {
+        handle_place_bet(
+            ctx,
+            bet_size,
+            side,
+            expected_game,
+            min_multiplier,
+            max_pool_share,
+        )
+    }
+
+    pub fn place_limit_order(
+        ctx: Context<PlaceLimitOrder>,
+        amount: u64,
+        side: u8,
+        min_multiplier: f64,
+    ) -> Result<()> {
+        handle_place_limit_order(ctx, amount, side, min_multiplier)
+    }
+
+    pub fn withdraw_limit_order(ctx: Context<WithdrawLimitOrder>) -> Result<()> {
+        handle_withdraw_limit_order(ctx)
+    }
+
+    pub fn cancel_bet(ctx: Context<CancelBet>) -> Result<()> {
+        handle_cancel_bet(ctx)
+    }
+
+    pub fn cash_out(ctx: Context<CashOut>, min_payout: u64) -> Result<()> {
+        handle_cash_out(ctx, min_payout)
     }
 
-    pub fn start_anticipation(ctx: Context<StartAnticipation>) -> Result<()> {
+    pub fn commit_bet(ctx: Context<CommitBet>, commitment: [u8; 32], amount: u64) -> Result<()> {
+        handle_commit_bet(ctx, commitment, amount)
+    }
+
+    pub fn reveal_bet(ctx: Context<RevealBet>, side: u8, salt: [u8; 32]) -> Result<()> {
+        handle_reveal_bet(ctx, side, salt)
+    }
+
+    pub fn refund_commitment(ctx: Context<RefundCommitment>) -> Result<()> {
+        handle_refund_commitment(ctx)
+    }
+
+    pub fn start_anticipation<'info>(
+        ctx: Context<'_, '_, '_, 'info, StartAnticipation<'info>>,
+    ) -> Result<()> {
         handle_start_anticipation(ctx)
     }
 
-    pub fn resolve_game(ctx: Context<ResolveBet>) -> Result<()> {
+    pub fn resolve_game<'info>(ctx: Context<'_, '_, '_, 'info, ResolveBet<'info>>) -> Result<()> {
         handle_resolve_game(ctx)
     }
 
+    pub fn crank_tick<'info>(ctx: Context<'_, '_, '_, 'info, CrankTick<'info>>) -> Result<()> {
+        handle_crank_tick(ctx)
+    }
+
+    pub fn challenge_resolution(ctx: Context<ChallengeResolution>) -> Result<()> {
+        handle_challenge_resolution(ctx)
+    }
+
+    pub fn finalize_game(ctx: Context<FinalizeGame>) -> Result<()> {
+        handle_finalize_game(ctx)
+    }
+
     pub fn claim_win(ctx: Context<ClaimWin>) -> Result<()> {
         handle_claim_win(ctx)
     }
@@ -62,6 +124,71 @@ mod mulberry_quick_bets {
         )
     }
 
+    pub fn change_crank_rewards(
+        ctx: Context<ChangeCrankRewards>,
+        crank_reward: u64,
+        crank_fee_share: u64,
+    ) -> Result<()> {
+        handle_change_crank_rewards(ctx, crank_reward, crank_fee_share)
+    }
+
+    pub fn change_cancellation_fee(
+        ctx: Context<ChangeCancellationFee>,
+        cancellation_fee: u64,
+    ) -> Result<()> {
+        handle_change_cancellation_fee(ctx, cancellation_fee)
+    }
+
+    pub fn change_cash_out_model(
+        ctx: Context<ChangeCashOutModel>,
+        cash_out_enabled: bool,
+        cash_out_sensitivity: u64,
+        cash_out_haircut: u64,
+    ) -> Result<()> {
+        handle_change_cash_out_model(
+            ctx,
+            cash_out_enabled,
+            cash_out_sensitivity,
+            cash_out_haircut,
+        )
+    }
+
+    pub fn change_commit_reveal(
+        ctx: Context<ChangeCommitReveal>,
+        commit_reveal: bool,
+        reveal_time: u64,
+        forfeit_unrevealed: bool,
+    ) -> Result<()> {
+        handle_change_commit_reveal(ctx, commit_reveal, reveal_time, forfeit_unrevealed)
+    }
+
+    pub fn change_settlement_timing(
+        ctx: Context<ChangeSettlementTiming>,
+        settlement_jitter: u64,
+        publish_time_tolerance: u64,
+    ) -> Result<()> {
+        handle_change_settlement_timing(ctx, settlement_jitter, publish_time_tolerance)
+    }
+
+    pub fn change_dispute_settings(
+        ctx: Context<ChangeDisputeSettings>,
+        dispute_window: u64,
+        challenge_bond: u64,
+    ) -> Result<()> {
+        handle_change_dispute_settings(ctx, dispute_window, challenge_bond)
+    }
+
+    pub fn set_market_config(ctx: Context<SetMarketConfig>, market: MarketConfig) -> Result<()> {
+        handle_set_market_config(ctx, market)
+    }
+
+    pub fn change_max_games_in_progress(
+        ctx: Context<ChangeMaxGamesInProgress>,
+        max_games_in_progress: u8,
+    ) -> Result<()> {
+        handle_change_max_games_in_progress(ctx, max_games_in_progress)
+    }
+
     pub fn close_game(ctx: Context<CloseGame>) -> Result<()> {
         handle_close_game(ctx)
     }
@@ -70,12 +197,15 @@ mod mulberry_quick_bets {
         ctx.accounts
             .global_state
             .confirm_crank_admin(&ctx.accounts.signer)?;
-        for _ in 0..5 {
-            ctx.accounts.global_state.add_game_record(Pubkey::default());
-        }
+        ctx.accounts.global_state.clear_game_records()
+    }
+
+    pub fn cancel_game<'info>(ctx: Context<'_, '_, '_, 'info, CancelGame<'info>>) -> Result<()> {
+        handle_cancel_game(ctx)
+    }
 
-        ctx.accounts.global_state.to_close = Pubkey::default();
-        Ok(())
+    pub fn emergency_refund(ctx: Context<EmergencyRefund>) -> Result<()> {
+        handle_emergency_refund(ctx)
     }
 
     pub fn change_account_size(ctx: Context<ChangeAccountSize>, new_size: u64) -> Result<()> {
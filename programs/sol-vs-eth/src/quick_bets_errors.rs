@@ -59,5 +59,67 @@ pub enum QuickBetsErrors {
     #[msg("Reward Already Claimed")]
     RewardAlreadyClaimed,
     #[msg("Limit Reached, You can't buy more of this item")]
-    LimitReached
+    LimitReached,
+    #[msg("Invalid game status for this action")]
+    InvalidGameStatus,
+    #[msg("Too early to refund, the game can still be resolved")]
+    RefundTooSoon,
+    #[msg("Game isn't a running game of the series")]
+    GameMismatch,
+    #[msg("Nothing to crank yet")]
+    NothingToCrank,
+    #[msg("The bet was meant for another game")]
+    WrongGame,
+    #[msg("Odds moved past your limit")]
+    SlippageExceeded,
+    #[msg("The house can't guarantee the minimum multiplier with this bet")]
+    MultiplierGuaranteeBroken,
+    #[msg("You already have a limit order on this game")]
+    LimitOrderExists,
+    #[msg("No limit order found")]
+    NoLimitOrderFound,
+    #[msg("The limit order can't be withdrawn")]
+    LimitOrderNotWithdrawable,
+    #[msg("Cash-out is disabled")]
+    CashOutDisabled,
+    #[msg("Sides are committed in this game, use commit_bet")]
+    CommitRevealOnly,
+    #[msg("This game doesn't take commitments")]
+    NotCommitRevealGame,
+    #[msg("You already committed a bet on this game")]
+    CommitmentExists,
+    #[msg("No commitment found")]
+    NoCommitmentFound,
+    #[msg("Side and salt don't match the commitment")]
+    InvalidReveal,
+    #[msg("Not in the reveal window")]
+    RevealInactive,
+    #[msg("The commitment can't be refunded")]
+    CommitmentNotRefundable,
+    #[msg("Invalid slot hashes sysvar")]
+    InvalidSlotHashes,
+    #[msg("No oracle price published close enough to the settlement time")]
+    NoPriceNearSettlement,
+    #[msg("The settlement isn't final yet")]
+    SettlementNotFinal,
+    #[msg("The dispute window is closed")]
+    DisputeWindowClosed,
+    #[msg("Not available on this market type")]
+    UnsupportedMarket,
+    #[msg("The house can't take more exposure on this game")]
+    HouseExposureExceeded,
+    #[msg("Not a fully verified price update of the feed")]
+    InvalidPriceUpdate,
+    #[msg("No price update to challenge the resolution with")]
+    NoPriceUpdate,
+    #[msg("The publish time tolerance isn't set")]
+    PublishTimeToleranceUnset,
+    #[msg("The oracle price isn't positive")]
+    InvalidPrice,
+    #[msg("A bet on fixed odds needs the minimum multiplier it accepts")]
+    MinMultiplierRequired,
+    #[msg("The slot the settlement jitter is drawn from isn't reached yet")]
+    JitterSlotPending,
+    #[msg("Every open limit order of the game has to be settled")]
+    LimitOrdersMissing,
 }
\ No newline at end of file
@@ -1,8 +1,12 @@
+use anchor_lang::solana_program::hash::hashv;
+
+use crate::consts::{EMERGENCY_REFUND_TIMEOUT, MARGIN_OF_ERROR};
 use crate::quick_bets_errors::QuickBetsErrors;
 
 use super::*;
 
 #[account]
+#[derive(Default)]
 pub struct GlobalState {
     /// The base fee that's going to be charged on all bets on the 1 minute markets.
     /// every 100 is a 1% fee.
@@ -40,6 +44,65 @@ pub struct GlobalState {
 
     // the maximum bet size from a user
     pub max_user_bet: u64,
+
+    // cumulative counters across every game, updated by the game handlers.
+    pub stats: ProtocolStats,
+
+    // id of the next game to be started, every game is a PDA derived from its id.
+    pub game_counter: u64,
+
+    // paid to whoever calls start_game, start_anticipation or resolve_game.
+    pub crank_reward: u64,
+
+    // the part of the betting fees that funds the crank rewards, every 100 is 1% of the fee.
+    pub crank_fee_share: u64,
+
+    // fees set aside for crank rewards, held in the house wallet.
+    pub crank_reward_pool: u64,
+
+    // how many games can run at once, above 1 the next game opens its betting as soon as the
+    // current one enters anticipation. 0 and 1 both mean no overlap.
+    pub max_games_in_progress: u8,
+
+    // kept from the stake of a bet cancelled during the betting, every 100 is 1%.
+    pub cancellation_fee: u64,
+
+    // cash-out during the anticipation, a bet is valued at its payout times the odds of winning,
+    // which start at 50% and move by cash_out_sensitivity (every 100 is 1%) for each 1% the
+    // assets moved apart. cash_out_haircut is then kept by the house, every 100 is 1%.
+    pub cash_out_enabled: bool,
+    pub cash_out_sensitivity: u64,
+    pub cash_out_haircut: u64,
+
+    // games hide the sides during the betting, users commit a hash of their side and reveal it
+    // within reveal_time seconds after the betting closes. Commitments that aren't revealed are
+    // kept by the house if forfeit_unrevealed, otherwise refunded.
+    pub commit_reveal: bool,
+    pub reveal_time: u64,
+    pub forfeit_unrevealed: bool,
+
+    // the settlement happens a random number of seconds, up to settlement_jitter, after the end
+    // of the anticipation time.
+    pub settlement_jitter: u64,
+
+    // how far from the scheduled end, in seconds, a price can be published to settle a game.
+    pub publish_time_tolerance: u64,
+
+    // seconds after the resolution during which it can be challenged, payouts wait for it to end.
+    // 0 turns the disputes off.
+    pub dispute_window: u64,
+    // posted by a challenger, forfeited to the house if the challenge fails
+    pub challenge_bond: u64,
+
+    // the rules of the games started from now on
+    pub market: MarketConfig,
+
+    // pools of draws rolled into the next game, held in the house wallet until it starts
+    pub rollover_pool: u64,
+
+    // the top ups the house is committed to across the games still taking bets, set aside in
+    // the house wallet so every game's guarantee holds at once
+    pub reserved_top_ups: u64,
 }
 
 impl GlobalState {
@@ -51,6 +114,34 @@ impl GlobalState {
         Ok(())
     }
 
+    /// A new game can start once no game is taking bets anymore,
+    /// and fewer than max_games_in_progress games are still running.
+    pub fn require_can_start_game(&self) -> Result<()> {
+        let mut games_in_progress = 0;
+        for game in self.game_records.iter() {
+            if game.status.is_finished() || game.game_address == Pubkey::default() {
+                continue;
+            }
+            if game.status == GameStatus::Betting {
+                return Err(QuickBetsErrors::GameInProgress.into());
+            }
+            games_in_progress += 1;
+        }
+        if games_in_progress >= std::cmp::max(self.max_games_in_progress, 1) {
+            return Err(QuickBetsErrors::GameInProgress.into());
+        }
+        Ok(())
+    }
+
+    /// hands out the id of a new game and records it.
+    pub fn register_new_game(&mut self, game_address: Pubkey) -> u64 {
+        let game_id = self.game_counter;
+        self.game_counter += 1;
+        self.stats.total_games += 1;
+        self.add_game_record(game_address);
+        game_id
+    }
+
     pub fn add_game_record(&mut self, game_address: Pubkey) {
         let mut game_records = self.game_records.clone();
         game_records.rotate_right(1);
@@ -62,6 +153,93 @@ impl GlobalState {
         self.game_records = game_records;
     }
 
+    pub fn record_bet(
+        &mut self,
+        market_type: &MarketType,
+        side: u8,
+        amount: u64,
+        fee: u64,
+        house_matched: u64,
+    ) {
+        self.stats.total_bets += 1;
+        if market_type.pooled_by_outcome() {
+            self.stats.total_outcome_volume += amount;
+        } else if side == 0 {
+            self.stats.total_sol_volume += amount;
+        } else {
+            self.stats.total_eth_volume += amount;
+        }
+        self.stats.fees_collected += fee;
+        self.stats.house_matched_volume += house_matched;
+    }
+
+    /// takes a cancelled bet, placed in placements bets, back out of the counters. The fees it
+    /// paid were kept and its house match is taken out when it's returned to the house.
+    pub fn unrecord_bet(
+        &mut self,
+        market_type: &MarketType,
+        side: u8,
+        amount: u64,
+        placements: u64,
+    ) {
+        self.stats.total_bets -= placements;
+        if market_type.pooled_by_outcome() {
+            self.stats.total_outcome_volume -= amount;
+        } else if side == 0 {
+            self.stats.total_sol_volume -= amount;
+        } else {
+            self.stats.total_eth_volume -= amount;
+        }
+    }
+
+    /// house_payout is what the house got back from the vault, house_stake is everything it put in.
+    pub fn record_resolution(&mut self, draw: bool, voided: bool, house_payout: u64, house_stake: u64) {
+        if voided {
+            self.stats.total_voids += 1;
+        } else if draw {
+            self.stats.total_draws += 1;
+        }
+        self.stats.house_pnl += house_payout as i64 - house_stake as i64;
+    }
+
+    /// the reward for the current crank step, never more than what the fees have set aside.
+    pub fn take_crank_reward(&mut self) -> u64 {
+        let reward = std::cmp::min(self.crank_reward, self.crank_reward_pool);
+        self.crank_reward_pool -= reward;
+        self.stats.crank_rewards_paid += reward;
+        reward
+    }
+
+    /// what the next game gets from the draws before it.
+    pub fn take_rollover_pool(&mut self) -> u64 {
+        std::mem::take(&mut self.rollover_pool)
+    }
+
+    /// What's left in a house wallet holding house_wallet_amount for that game, once the top ups
+    /// reserved for every other game are set aside.
+    pub fn house_available_for(&self, game: &Game, house_wallet_amount: u64) -> u64 {
+        house_wallet_amount.saturating_sub(self.reserved_top_ups - game.reserved_top_up)
+    }
+
+    /// Reserves the top up the game needs with its current bets, fails if the house can't cover
+    /// it on top of what it's committed to for the other games.
+    pub fn reserve_top_up(&mut self, game: &mut Game, house_wallet_amount: u64) -> Result<()> {
+        let (_, top_up) = game.required_top_up();
+        require!(
+            top_up <= game.house_capacity_left()
+                && top_up <= self.house_available_for(game, house_wallet_amount),
+            QuickBetsErrors::MultiplierGuaranteeBroken
+        );
+        self.reserved_top_ups = self.reserved_top_ups - game.reserved_top_up + top_up;
+        game.reserved_top_up = top_up;
+        Ok(())
+    }
+
+    /// once the game put its top up in or won't need it anymore.
+    pub fn release_top_up(&mut self, game: &mut Game) {
+        self.reserved_top_ups -= std::mem::take(&mut game.reserved_top_up);
+    }
+
     pub fn modify_game_record(&mut self, game_address: Pubkey, status: GameStatus) {
         for game_record in self.game_records.iter_mut() {
             if game_record.game_address == game_address {
@@ -70,6 +248,153 @@ impl GlobalState {
             }
         }
     }
+
+    /// frees the slot of a game that will never be resolved, so a new game can take its place.
+    pub fn is_recorded(&self, game_address: Pubkey) -> bool {
+        self.game_records
+            .iter()
+            .any(|game_record| game_record.game_address == game_address)
+    }
+
+    pub fn remove_game_record(&mut self, game_address: Pubkey) {
+        for game_record in self.game_records.iter_mut() {
+            if game_record.game_address == game_address {
+                *game_record = GameRecord::default();
+                return;
+            }
+        }
+    }
+
+    /// Empties the records, only once every recorded game is closed or cancelled. A game still
+    /// running would no longer be cranked and keep its top up reserved.
+    pub fn clear_game_records(&mut self) -> Result<()> {
+        let running = self.game_records.iter().any(|game| {
+            game.game_address != Pubkey::default()
+                && !matches!(game.status, GameStatus::Closed | GameStatus::Cancelled)
+        });
+        require!(!running, QuickBetsErrors::GameInProgress);
+        self.game_records = Default::default();
+        self.to_close = Pubkey::default();
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct ProtocolStats {
+    pub total_games: u64,
+    pub total_bets: u64,
+
+    // user volume on each side, in betting token lots
+    pub total_sol_volume: u64,
+    pub total_eth_volume: u64,
+    // user volume on race and bucket markets, across every outcome
+    pub total_outcome_volume: u64,
+
+    pub fees_collected: u64,
+
+    // everything the house put in the vaults, both when matching bets and on the anticipation top up
+    pub house_matched_volume: u64,
+
+    // what the house got back from resolved games minus what it put in them
+    pub house_pnl: i64,
+
+    // games that ended with both assets moving the same,
+    // and games resolved too late that fell back to the initial prices.
+    pub total_draws: u64,
+    pub total_voids: u64,
+
+    pub crank_rewards_paid: u64,
+
+    // paid by the house wallet for bets cashed out during the anticipation
+    pub cash_out_paid: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct MarketConfig {
+    // a relative move within draw_band of zero is a draw, every 100 is 1%
+    pub draw_band: u64,
+    pub draw_policy: DrawPolicy,
+    // kept from every stake on a draw with DrawPolicy::HouseFee, every 100 is 1%
+    pub draw_fee: u64,
+    // what sol has to outperform eth by to win, every 100 is 1%. Negative when eth has to
+    // outperform sol, e.g. -5 for "eth beats sol by 0.05%". On an up/down market, how much the
+    // asset has to go up for up to win.
+    pub handicap: i64,
+    pub market_type: MarketType,
+    // the asset of an up/down market, 0 sol and 1 eth
+    pub up_down_asset: u8,
+    // the feeds of a race market in the order of its outcomes, only the first race_feed_count
+    // are used
+    pub race_feeds: [Pubkey; 8],
+    pub race_feed_count: u8,
+    // what the house puts on every outcome of a race or bucket market when the game starts
+    pub outcome_seed: u64,
+    // the edges between the buckets of a bucket market in increasing order, compared with how
+    // much sol outperformed eth past the handicap, every 100 is 1%. Bucket 0 is below the first
+    // edge and the last bucket at or above the last one, only the first bucket_count - 1 are used.
+    pub bucket_edges: [i64; 7],
+    pub bucket_count: u8,
+    pub payout_mode: PayoutMode,
+    // what the house quotes a bet of a fixed odds market, stake included, before any exposure
+    pub fixed_multiplier: f64,
+    // how much of the part of the quote above the stake a side loses once the cover the house
+    // put against it reaches max_house_bet_size, every 100 is 1%
+    pub fixed_odds_skew: u64,
+    // how much of its pool share a bet placed at the very end of the betting loses, following
+    // decay_curve for bets placed earlier. Every 100 is 1%, 0 turns it off.
+    pub late_bet_decay: u64,
+    pub decay_curve: DecayCurve,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub enum DecayCurve {
+    // the loss grows with the time elapsed in the betting
+    #[default]
+    Linear,
+    // the loss grows with the square of the time elapsed, early bets barely lose anything
+    Quadratic,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub enum PayoutMode {
+    // the winners share the pool
+    #[default]
+    Parimutuel,
+    // every winner gets its stake times the multiplier locked when it bet, the house is the
+    // counterparty of every bet
+    FixedOdds,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub enum MarketType {
+    // side 0 bets sol outperforms eth, side 1 that eth does
+    #[default]
+    Duel,
+    // side 0 bets the asset goes up from the start price, side 1 that it goes down
+    UpDown,
+    // every side is one of the race feeds, betting that it gains the most
+    Race,
+    // every side is a range of how much sol outperforms eth, from bucket_edges
+    Buckets,
+}
+
+impl MarketType {
+    /// the market has a pool for each of its outcomes instead of sol_bet_size and eth_bet_size,
+    /// the house seeds them instead of matching bets and topping up.
+    pub fn pooled_by_outcome(&self) -> bool {
+        matches!(self, MarketType::Race | MarketType::Buckets)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub enum DrawPolicy {
+    // every bet is returned
+    #[default]
+    Refund,
+    // every bet is returned minus the draw_fee
+    HouseFee,
+    // the users' stakes go to the winners of the next game
+    RollOver,
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
@@ -78,12 +403,44 @@ pub struct GameRecord {
     pub status: GameStatus,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, PartialEq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
 pub enum GameStatus {
     #[default]
     Betting,
     Anticipation,
     Resolved,
+    // resolved too late, settled with the initial prices so every bet is returned
+    Voided,
+    // the game accounts are closed, only kept in the records
+    Closed,
+    // stopped before settlement, every stake is refunded
+    Cancelled,
+}
+
+impl GameStatus {
+    /// Betting -> Anticipation -> Resolved/Voided -> Closed,
+    /// a game that isn't settled yet can also be Cancelled and then Closed.
+    pub fn can_transition_to(&self, next: &GameStatus) -> bool {
+        matches!(
+            (self, next),
+            (GameStatus::Betting, GameStatus::Anticipation)
+                | (GameStatus::Anticipation, GameStatus::Resolved)
+                | (GameStatus::Anticipation, GameStatus::Voided)
+                | (GameStatus::Betting, GameStatus::Cancelled)
+                | (GameStatus::Anticipation, GameStatus::Cancelled)
+                | (GameStatus::Resolved, GameStatus::Closed)
+                | (GameStatus::Voided, GameStatus::Closed)
+                | (GameStatus::Cancelled, GameStatus::Closed)
+        )
+    }
+
+    /// no more bets or price updates can happen, a new game can be started.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            GameStatus::Resolved | GameStatus::Voided | GameStatus::Closed | GameStatus::Cancelled
+        )
+    }
 }
 
 #[account]
@@ -122,48 +479,735 @@ pub struct Game {
 
     // a max of 25 users can bet on each side.
     pub user_bets: [UserBet; 20],
+
+    // the game's number in the series, used as the seed of the game PDA
+    pub game_id: u64,
+
+    // only ever changed through Game::transition
+    pub status: GameStatus,
+
+    // min_multiplier and max_house_bet_size when the game started, the multiplier is guaranteed
+    // to every winner so it can't change while bets are open.
+    pub min_multiplier: f64,
+    pub max_house_bet_size: u64,
+
+    // stakes escrowed in the vault until the anticipation, joining the pool only if the odds
+    // end up good enough for them. Each order has its own account, the game counts the open
+    // ones so none can be left out of the anticipation, and the stakes of the orders that
+    // never joined the pool and are still in the vault.
+    pub open_order_count: u64,
+    pub unfilled_orders_amount: u64,
+
+    // the commit-reveal settings when the game started, reveal_time is 0 for regular games
+    pub commit_reveal: bool,
+    pub reveal_time: u64,
+    pub forfeit_unrevealed: bool,
+    // stakes of the commitments that weren't revealed or refunded yet, each commitment has its
+    // own account
+    pub committed_amount: u64,
+
+    // settlement time, the end of the anticipation time until the settlement adds the jitter to
+    // it. The final prices are the oracle observations nearest to it.
+    pub scheduled_end: u64,
+
+    // when the final prices were published
+    pub sol_publish_time: i64,
+    pub eth_publish_time: i64,
+
+    // dispute_window when the game started, and the end of the window once it's resolved
+    pub dispute_window: u64,
+    pub dispute_end: u64,
+    // the settlement can't change anymore and the house took its share
+    pub finalized: bool,
+
+    // the market config when the game started
+    pub market: MarketConfig,
+    // rolled over from previous draws, paid to the winners on top of the pool
+    pub rollover_bonus: u64,
+
+    // the pool of every outcome of a race or bucket market
+    pub outcome_pools: [u64; 8],
+    // the initial and final price of every outcome of a race
+    pub race_initial_prices: [u64; 8],
+    pub race_final_prices: [u64; 8],
+
+    // the part of the global reserved_top_ups that's for this game
+    pub reserved_top_up: u64,
+
+    // committed when the anticipation starts, the settlement jitter comes from the hash of this
+    // slot and is up to settlement_jitter when the anticipation started
+    pub jitter_slot: u64,
+    pub settlement_jitter: u64,
 }
 
 impl Game {
     /// 0 meaning that sol had won,
     /// 1 meaning that eth had won
     /// 2 meaning that it's a draw
+    /// on an up/down market 0 is up and 1 is down. On a race it's the outcome of the asset that
+    /// gained the most, on a bucket market the bucket of the outperformance, and a draw is then the
+    /// side after the last outcome.
     pub fn get_winner(&self) -> u8 {
-        let sol_change = (self.final_sol_price as i64 - self.initial_sol_price as i64) as f64
-            / self.initial_sol_price as f64;
-        let eth_change = (self.final_eth_price as i64 - self.initial_eth_price as i64) as f64
-            / self.initial_eth_price as f64;
+        match self.market.market_type {
+            MarketType::Race => return self.race_winner(),
+            MarketType::Buckets => return self.bucket_winner(),
+            _ => {}
+        }
+        let lead = self.lead(self.final_sol_price, self.final_eth_price);
 
-        if sol_change == eth_change {
-            2
-        } else if eth_change > sol_change {
+        if lead.abs() <= self.market.draw_band as f64 {
+            self.draw_outcome()
+        } else if lead < 0.0 {
             1
         } else {
             0
         }
     }
 
-    pub fn calculate_winning_amount(&self, amount: u64, side: u8) -> u64 {
-        let total_pool_size = self.sol_bet_size + self.eth_bet_size;
-        let total_sol_bets = self.sol_bet_size;
-        let total_eth_bets = self.eth_bet_size;
+    /// How far side 0 is ahead past the handicap with these prices, every 100 is 1%.
+    /// On a duel that's how much sol outperformed eth, on an up/down market how much the asset
+    /// went up since the start.
+    pub fn lead(&self, sol_price: u64, eth_price: u64) -> f64 {
+        let sol_change = (sol_price as i64 - self.initial_sol_price as i64) as f64
+            / self.initial_sol_price as f64;
+        let eth_change = (eth_price as i64 - self.initial_eth_price as i64) as f64
+            / self.initial_eth_price as f64;
+        let change = match self.market.market_type {
+            MarketType::UpDown if self.market.up_down_asset == 0 => sol_change,
+            MarketType::UpDown => eth_change,
+            _ => sol_change - eth_change,
+        };
+        change * 100.0 * 100.0 - self.market.handicap as f64
+    }
+
+    /// The outcome whose asset gained the most, it's a draw if another one is within the draw
+    /// band of it.
+    fn race_winner(&self) -> u8 {
+        let changes: Vec<f64> = (0..self.outcome_count() as usize)
+            .map(|side| {
+                let initial_price = self.race_initial_prices[side];
+                (self.race_final_prices[side] as i64 - initial_price as i64) as f64
+                    / initial_price as f64
+                    * 100.0
+                    * 100.0
+            })
+            .collect();
+        let mut winner = 0;
+        for (side, change) in changes.iter().enumerate() {
+            if *change > changes[winner] {
+                winner = side;
+            }
+        }
+        let contested = changes.iter().enumerate().any(|(side, change)| {
+            side != winner && changes[winner] - change <= self.market.draw_band as f64
+        });
+        if contested {
+            self.draw_outcome()
+        } else {
+            winner as u8
+        }
+    }
+
+    /// The bucket how much sol outperformed eth falls in, it's a draw if that's within the draw
+    /// band of an edge. Without a band a lead on an edge is in the bucket above it.
+    fn bucket_winner(&self) -> u8 {
+        let lead = self.lead(self.final_sol_price, self.final_eth_price);
+        let edges = &self.market.bucket_edges[..self.outcome_count() as usize - 1];
+        let on_edge = self.market.draw_band > 0
+            && edges
+                .iter()
+                .any(|edge| (lead - *edge as f64).abs() <= self.market.draw_band as f64);
+        if on_edge {
+            return self.draw_outcome();
+        }
+        edges.iter().filter(|edge| lead >= **edge as f64).count() as u8
+    }
+
+    /// how many sides can be bet on, 2 unless it's a race or bucket market.
+    pub fn outcome_count(&self) -> u8 {
+        match self.market.market_type {
+            MarketType::Race => self.market.race_feed_count,
+            MarketType::Buckets => self.market.bucket_count,
+            _ => 2,
+        }
+    }
+
+    /// what get_winner returns on a draw, the side after the last outcome.
+    pub fn draw_outcome(&self) -> u8 {
+        self.outcome_count()
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.get_winner() == self.draw_outcome()
+    }
+
+    /// the pool on that side, sol_bet_size and eth_bet_size unless it's pooled by outcome.
+    pub fn side_size(&self, side: u8) -> u64 {
+        if self.market.market_type.pooled_by_outcome() {
+            self.outcome_pools[side as usize]
+        } else if side == 0 {
+            self.sol_bet_size
+        } else {
+            self.eth_bet_size
+        }
+    }
+
+    pub fn add_to_side(&mut self, side: u8, amount: u64) {
+        if self.market.market_type.pooled_by_outcome() {
+            self.outcome_pools[side as usize] += amount;
+        } else if side == 0 {
+            self.sol_bet_size += amount;
+        } else {
+            self.eth_bet_size += amount;
+        }
+    }
+
+    pub fn remove_from_side(&mut self, side: u8, amount: u64) {
+        if self.market.market_type.pooled_by_outcome() {
+            self.outcome_pools[side as usize] -= amount;
+        } else if side == 0 {
+            self.sol_bet_size -= amount;
+        } else {
+            self.eth_bet_size -= amount;
+        }
+    }
+
+    /// every side together, without the rollover bonus.
+    pub fn total_pool_size(&self) -> u64 {
+        (0..self.outcome_count()).map(|side| self.side_size(side)).sum()
+    }
+
+    /// a draw under DrawPolicy::RollOver, nobody gets paid and the pool goes to the next game.
+    pub fn pool_rolls_over(&self) -> bool {
+        self.status == GameStatus::Resolved
+            && self.market.draw_policy == DrawPolicy::RollOver
+            && self.is_draw()
+    }
+
+    /// A winner gets the pool in proportion to the effective amount of their bet against the
+    /// effective size of the winning side, or its locked multiplier on fixed odds. Draws follow
+    /// the draw policy and voided or cancelled games return the stake.
+    pub fn calculate_winning_amount(&self, user_bet: &UserBet) -> u64 {
+        if self.status == GameStatus::Cancelled || self.status == GameStatus::Voided {
+            return user_bet.amount;
+        }
+        let total_pool_size = self.total_pool_size() + self.rollover_bonus;
         let game_winner = self.get_winner();
-        if side != game_winner && game_winner != 2 {
+        if game_winner == self.draw_outcome() {
+            return match self.market.draw_policy {
+                DrawPolicy::Refund => user_bet.amount,
+                DrawPolicy::HouseFee => {
+                    let fee = user_bet.amount as f64 * self.market.draw_fee as f64 / 100.0 / 100.0;
+                    user_bet.amount - fee as u64
+                }
+                DrawPolicy::RollOver => 0,
+            };
+        } else if user_bet.side != game_winner {
             return 0;
-        } else if game_winner == 2 {
-            return amount;
-        }
-        if side == game_winner && side == 0 {
-            // this means the user bet on sol, and sol won
-            let user_pool_share = amount as f64 / total_sol_bets as f64;
-            (total_pool_size as f64 * user_pool_share).floor() as u64
-        } else if side == game_winner && side == 1 {
-            // this means the user bet on eth, and eth won
-            let user_pool_share = amount as f64 / total_eth_bets as f64;
-            (total_pool_size as f64 * user_pool_share).floor() as u64
+        }
+        if self.market.payout_mode == PayoutMode::FixedOdds {
+            return (user_bet.amount as f64 * user_bet.locked_multiplier).floor() as u64;
+        }
+        let user_pool_share =
+            user_bet.effective_amount as f64 / self.effective_side_size(user_bet.side) as f64;
+        (total_pool_size as f64 * user_pool_share).floor() as u64
+    }
+
+    /// The size of a side with every user bet counted at its effective amount, the house's stake
+    /// always counts in full.
+    pub fn effective_side_size(&self, side: u8) -> u64 {
+        let side_size = self.side_size(side);
+        let late_bet_discount: u64 = self
+            .user_bets
+            .iter()
+            .filter(|user_bet| user_bet.owner != Pubkey::default() && user_bet.side == side)
+            .map(|user_bet| user_bet.amount - user_bet.effective_amount)
+            .sum();
+        side_size - late_bet_discount
+    }
+
+    /// The share of its amount a bet placed at that time counts for, going down along the
+    /// market's decay curve from 1 at the start of the betting to 1 - late_bet_decay at its end.
+    pub fn bet_weight(&self, placed_at: u64, betting_time: u64) -> f64 {
+        if betting_time == 0 {
+            return 1.0;
+        }
+        let elapsed = placed_at.saturating_sub(self.betting_start) as f64 / betting_time as f64;
+        let decay = match self.market.decay_curve {
+            DecayCurve::Linear => elapsed.min(1.0),
+            DecayCurve::Quadratic => elapsed.min(1.0).powi(2),
+        };
+        1.0 - self.market.late_bet_decay as f64 / 100.0 / 100.0 * decay
+    }
+
+    /// The lowest share any user bet on that side counts for, 1 if there's none.
+    pub fn min_bet_weight(&self, side: u8) -> f64 {
+        self.user_bets
+            .iter()
+            .filter(|user_bet| {
+                user_bet.owner != Pubkey::default() && user_bet.side == side && user_bet.amount > 0
+            })
+            .map(|user_bet| user_bet.effective_amount as f64 / user_bet.amount as f64)
+            .fold(1.0, f64::min)
+    }
+
+    /// what that bet would get per lot if its side won with the current pool sizes.
+    pub fn bet_multiplier(&self, user_bet: &UserBet) -> f64 {
+        if self.market.payout_mode == PayoutMode::FixedOdds {
+            return user_bet.locked_multiplier;
+        }
+        (self.total_pool_size() + self.rollover_bonus) as f64
+            * user_bet.effective_amount as f64
+            / (self.effective_side_size(user_bet.side) as f64 * user_bet.amount as f64)
+    }
+
+    pub fn start(
+        &mut self,
+        game_id: u64,
+        game_vault: Pubkey,
+        global_state: &GlobalState,
+    ) -> Result<()> {
+        self.game_id = game_id;
+        self.min_multiplier = global_state.min_multiplier;
+        self.max_house_bet_size = global_state.max_house_bet_size;
+        self.commit_reveal = global_state.commit_reveal;
+        self.reveal_time = if global_state.commit_reveal {
+            global_state.reveal_time
         } else {
             0
+        };
+        self.forfeit_unrevealed = global_state.forfeit_unrevealed;
+        self.dispute_window = global_state.dispute_window;
+        self.market = global_state.market.clone();
+        self.status = GameStatus::Betting;
+        self.betting_start = Clock::get()?.unix_timestamp as u64;
+        self.eth_bet_size = 0;
+        self.sol_bet_size = 0;
+        self.outcome_pools = [0; 8];
+        self.game_vault = game_vault;
+        Ok(())
+    }
+
+    /// The house puts outcome_seed on every outcome of a race or bucket market so the first bets
+    /// already have odds, as much as max_house_bet_size allows. Returns what it put in.
+    pub fn seed_outcomes(&mut self) -> u64 {
+        if !self.market.market_type.pooled_by_outcome() {
+            return 0;
+        }
+        let outcomes = self.outcome_count();
+        let seed = std::cmp::min(
+            self.market.outcome_seed,
+            self.house_capacity_left() / outcomes as u64,
+        );
+        for side in 0..outcomes {
+            self.add_to_side(side, seed);
+        }
+        let seeded = seed * outcomes as u64;
+        self.house_bet_amount += seeded;
+        seeded
+    }
+
+    /// On fixed odds the house puts what a bet could win at that multiplier in the vault when
+    /// it's placed, so every payout is covered.
+    pub fn fixed_odds_cover(&self, amount: u64, multiplier: f64) -> u64 {
+        (amount as f64 * (multiplier - 1.0)).ceil() as u64
+    }
+
+    /// The multiplier the house quotes a new bet on that side of a fixed odds market. The part
+    /// above the stake shrinks by up to fixed_odds_skew as the cover the house already put against
+    /// that side fills max_house_bet_size.
+    pub fn quote_multiplier(&self, side: u8) -> f64 {
+        let exposure: u64 = self
+            .user_bets
+            .iter()
+            .filter(|user_bet| user_bet.owner != Pubkey::default() && user_bet.side == side)
+            .map(|user_bet| user_bet.house_matched)
+            .sum();
+        let exposure_share = if self.max_house_bet_size == 0 {
+            1.0
+        } else {
+            (exposure as f64 / self.max_house_bet_size as f64).min(1.0)
+        };
+        let skew = self.market.fixed_odds_skew as f64 / 100.0 / 100.0 * exposure_share;
+        1.0 + (self.market.fixed_multiplier - 1.0) * (1.0 - skew)
+    }
+
+    /// Closes the betting with the initial prices and tops up the pool so winners get at least
+    /// the min multiplier, returns how much the house added.
+    /// house_available is what the house wallet holds, limit orders are only filled if the house
+    /// can still cover the top up with them in. The slot the settlement jitter is drawn from is
+    /// committed, the bets are closed so nobody who can still bet knows its hash.
+    /// race_prices are the initial prices of a race, by outcome. orders are the open limit orders.
+    pub fn start_anticipation(
+        &mut self,
+        global_state: &GlobalState,
+        sol_price: u64,
+        eth_price: u64,
+        race_prices: [u64; 8],
+        orders: &mut [LimitOrder],
+        house_available: u64,
+    ) -> Result<u64> {
+        self.transition(GameStatus::Anticipation, global_state)?;
+        self.initial_sol_price = sol_price;
+        self.initial_eth_price = eth_price;
+        self.race_initial_prices = race_prices;
+
+        let clock = Clock::get()?;
+        self.anticipating_start = clock.unix_timestamp as u64;
+        self.scheduled_end = self.anticipating_start + global_state.anticipation_time;
+        // one slot a second, reached before the end of the anticipation time unless the slots
+        // take over a second, and still in the slot hashes when the game gets settled
+        self.jitter_slot = clock.slot + std::cmp::max(global_state.anticipation_time, 1);
+        self.settlement_jitter = global_state.settlement_jitter;
+
+        self.fill_limit_orders(orders, global_state, house_available);
+
+        // every bet was checked against the house capacity when it was placed
+        let (house_side, matched_amount) = self.required_top_up();
+        require!(
+            matched_amount <= self.house_capacity_left(),
+            QuickBetsErrors::MultiplierGuaranteeBroken
+        );
+        self.add_to_side(house_side, matched_amount);
+        self.house_bet_amount += matched_amount;
+        Ok(matched_amount)
+    }
+
+    /// The side and amount the house has to bet so that every winning bet, late ones included,
+    /// gets at least min_multiplier. The house bets against the side that's short. Markets pooled
+    /// by outcome are seeded when they start instead, and fixed odds are covered when the bets
+    /// are placed.
+    pub fn required_top_up(&self) -> (u8, u64) {
+        self.required_top_up_with(0, 0, 1.0)
+    }
+
+    /// the top up the house would need if that bet, counting for weight of its amount, was added
+    /// to the pool.
+    pub fn required_top_up_with(&self, side: u8, amount: u64, weight: f64) -> (u8, u64) {
+        if self.market.market_type.pooled_by_outcome()
+            || self.market.payout_mode == PayoutMode::FixedOdds
+        {
+            return (0, 0);
+        }
+        let mut side_sizes = [self.sol_bet_size, self.eth_bet_size];
+        let mut effective_sizes = [self.effective_side_size(0), self.effective_side_size(1)];
+        let mut min_weights = [self.min_bet_weight(0), self.min_bet_weight(1)];
+        if amount > 0 {
+            side_sizes[side as usize] += amount;
+            effective_sizes[side as usize] += (amount as f64 * weight) as u64;
+            min_weights[side as usize] = min_weights[side as usize].min(weight);
+        }
+        top_up_for(side_sizes, effective_sizes, min_weights, self.min_multiplier)
+    }
+
+    /// Goes through the open limit orders from the least demanding one, an order joins the pool
+    /// if the multiplier on its side after the house top up is still at or above its limit, without
+    /// pushing the orders filled before it below theirs. Every other order is left to be withdrawn.
+    pub fn fill_limit_orders(
+        &mut self,
+        orders: &mut [LimitOrder],
+        global_state: &GlobalState,
+        house_available: u64,
+    ) {
+        let mut order_slots: Vec<usize> = (0..orders.len())
+            .filter(|&slot| orders[slot].status == LimitOrderStatus::Open)
+            .collect();
+        order_slots.sort_by(|a, b| {
+            orders[*a]
+                .min_multiplier
+                .total_cmp(&orders[*b].min_multiplier)
+        });
+
+        for slot in order_slots {
+            self.open_order_count -= 1;
+            let order = orders[slot].clone();
+            if !self.limit_order_fits(&order, orders, global_state, house_available) {
+                orders[slot].status = LimitOrderStatus::Rejected;
+                continue;
+            }
+            self.add_to_side(order.side, order.amount);
+            // limit_order_fits made sure the bet has a slot and is on the same side
+            let _ = self.add_user_bet(
+                order.owner,
+                order.amount,
+                order.side,
+                order.placed_at,
+                global_state.betting_time,
+            );
+            orders[slot].status = LimitOrderStatus::Filled;
+            self.unfilled_orders_amount -= order.amount;
+        }
+    }
+
+    fn limit_order_fits(
+        &self,
+        order: &LimitOrder,
+        orders: &[LimitOrder],
+        global_state: &GlobalState,
+        house_available: u64,
+    ) -> bool {
+        let max_user_bet = global_state.max_user_bet;
+        match self.get_user_bet(order.owner) {
+            Some(user_bet) => {
+                if user_bet.side != order.side || user_bet.amount + order.amount > max_user_bet {
+                    return false;
+                }
+            }
+            None => {
+                let free_slot = self
+                    .user_bets
+                    .iter()
+                    .any(|user_bet| user_bet.owner == Pubkey::default());
+                if !free_slot || order.amount > max_user_bet {
+                    return false;
+                }
+            }
+        }
+
+        let order_weight = |order: &LimitOrder| {
+            self.bet_weight(order.placed_at, global_state.betting_time)
+        };
+        let (house_side, top_up) =
+            self.required_top_up_with(order.side, order.amount, order_weight(order));
+        if top_up > std::cmp::min(self.house_capacity_left(), house_available) {
+            return false;
+        }
+        let (mut sol_bet_size, mut eth_bet_size) = (self.sol_bet_size, self.eth_bet_size);
+        if order.side == 0 {
+            sol_bet_size += order.amount;
+        } else {
+            eth_bet_size += order.amount;
         }
+        if house_side == 0 {
+            sol_bet_size += top_up;
+        } else {
+            eth_bet_size += top_up;
+        }
+
+        // the late bet discount of each side, with this order in
+        let mut sol_discount = (self.sol_bet_size - self.effective_side_size(0)) as f64;
+        let mut eth_discount = (self.eth_bet_size - self.effective_side_size(1)) as f64;
+        if order.side == 0 {
+            sol_discount += order.amount as f64 * (1.0 - order_weight(order));
+        } else {
+            eth_discount += order.amount as f64 * (1.0 - order_weight(order));
+        }
+        let final_multiplier = |order: &LimitOrder| {
+            let effective_side_size = if order.side == 0 {
+                sol_bet_size as f64 - sol_discount
+            } else {
+                eth_bet_size as f64 - eth_discount
+            };
+            (sol_bet_size + eth_bet_size) as f64 * order_weight(order) / effective_side_size
+        };
+        final_multiplier(order) >= order.min_multiplier
+            && orders
+                .iter()
+                .filter(|filled| filled.status == LimitOrderStatus::Filled)
+                .all(|filled| final_multiplier(filled) >= filled.min_multiplier)
+    }
+
+    /// Returns the stake to give back for an order that isn't in the pool. An order can be taken
+    /// back while the betting is still open, once it's rejected, or if the game got cancelled
+    /// before it was filled. Filled orders are only closed.
+    pub fn withdraw_limit_order(&mut self, order: &LimitOrder) -> Result<u64> {
+        match order.status {
+            LimitOrderStatus::Open => {
+                require!(
+                    self.status == GameStatus::Betting || self.status == GameStatus::Cancelled,
+                    QuickBetsErrors::LimitOrderNotWithdrawable
+                );
+                self.open_order_count -= 1;
+            }
+            LimitOrderStatus::Rejected => {}
+            LimitOrderStatus::Filled => return Ok(0),
+        }
+        self.unfilled_orders_amount -= order.amount;
+        Ok(order.amount)
+    }
+
+    /// the window after the betting where commitments can be revealed.
+    pub fn reveal_active(&self, global_state: &GlobalState) -> Result<bool> {
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        let betting_end = self.betting_start + global_state.betting_time;
+        Ok(current_time > betting_end && current_time <= betting_end + self.reveal_time)
+    }
+
+    /// commitments nobody revealed by the anticipation are kept by the house if the game forfeits
+    /// them.
+    pub fn commitments_forfeited(&self) -> bool {
+        self.forfeit_unrevealed && self.anticipating_start > 0
+    }
+
+    /// An unrevealed commitment can be refunded once the reveal is over when the game doesn't
+    /// forfeit them, or if the game got cancelled. Returns the stake to give back, forfeited
+    /// commitments are only closed.
+    pub fn refund_commitment(&mut self, amount: u64) -> Result<u64> {
+        if self.commitments_forfeited() {
+            return Ok(0);
+        }
+        require!(
+            self.status == GameStatus::Cancelled || self.anticipating_start > 0,
+            QuickBetsErrors::CommitmentNotRefundable
+        );
+        self.committed_amount -= amount;
+        Ok(amount)
+    }
+
+    /// stakes of commitments that never joined the pool and are still owed to their owner.
+    pub fn unrevealed_amount(&self) -> u64 {
+        if self.commitments_forfeited() {
+            return 0;
+        }
+        self.committed_amount
+    }
+
+    /// how much more the house can put in this game.
+    pub fn house_capacity_left(&self) -> u64 {
+        self.max_house_bet_size.saturating_sub(self.house_bet_amount)
+    }
+
+    /// When the bettors can take their stake back if the game still isn't resolved, from its own
+    /// schedule so changing the settings doesn't move it for a running game. Before the
+    /// anticipation the scheduled end isn't known yet and it counts from the betting start.
+    pub fn emergency_refund_time(&self) -> u64 {
+        let expected_end = if self.scheduled_end > 0 {
+            self.scheduled_end
+        } else {
+            self.betting_start + self.reveal_time
+        };
+        expected_end + EMERGENCY_REFUND_TIMEOUT
+    }
+
+    /// a game that wasn't resolved within 15 seconds of its scheduled end gets voided.
+    pub fn resolution_expired(&self) -> Result<bool> {
+        Ok(self.scheduled_end + 15 < Clock::get()?.unix_timestamp as u64)
+    }
+
+    /// Settles the game with the final prices, or with the initial ones if it's voided.
+    /// Returns whether the game got voided.
+    pub fn resolve(
+        &mut self,
+        global_state: &GlobalState,
+        sol_price: u64,
+        eth_price: u64,
+        race_prices: [u64; 8],
+    ) -> Result<bool> {
+        let voided = self.resolution_expired()?;
+        if voided {
+            self.final_sol_price = self.initial_sol_price;
+            self.final_eth_price = self.initial_eth_price;
+            self.race_final_prices = self.race_initial_prices;
+            self.transition(GameStatus::Voided, global_state)?;
+        } else {
+            self.final_sol_price = sol_price;
+            self.final_eth_price = eth_price;
+            self.race_final_prices = race_prices;
+            self.transition(GameStatus::Resolved, global_state)?;
+        }
+        self.is_settled = true;
+        self.anticipating_end = Clock::get()?.unix_timestamp as u64;
+        // a voided game has nothing to dispute
+        self.dispute_end = if voided {
+            self.anticipating_end
+        } else {
+            self.anticipating_end + self.dispute_window
+        };
+        Ok(voided)
+    }
+
+    pub fn dispute_over(&self) -> Result<bool> {
+        Ok(self.dispute_end <= Clock::get()?.unix_timestamp as u64)
+    }
+
+    /// Replaces the final price of each feed whose challenged (price, publish time) was published
+    /// closer to the scheduled end. Returns whether any price got replaced.
+    pub fn apply_challenge(&mut self, sol: Option<(u64, i64)>, eth: Option<(u64, i64)>) -> bool {
+        let scheduled_end = self.scheduled_end as i64;
+        let mut replaced = false;
+        if let Some((sol_price, sol_publish_time)) = sol {
+            if sol_publish_time.abs_diff(scheduled_end)
+                < self.sol_publish_time.abs_diff(scheduled_end)
+            {
+                self.final_sol_price = sol_price;
+                self.sol_publish_time = sol_publish_time;
+                replaced = true;
+            }
+        }
+        if let Some((eth_price, eth_publish_time)) = eth {
+            if eth_publish_time.abs_diff(scheduled_end)
+                < self.eth_publish_time.abs_diff(scheduled_end)
+            {
+                self.final_eth_price = eth_price;
+                self.eth_publish_time = eth_publish_time;
+                replaced = true;
+            }
+        }
+        replaced
+    }
+
+    /// The only place a game changes stage, checks that the move is allowed from the current
+    /// status and that the timing for it is right.
+    pub fn transition(&mut self, next: GameStatus, global_state: &GlobalState) -> Result<()> {
+        require!(
+            self.status.can_transition_to(&next),
+            QuickBetsErrors::InvalidGameStatus
+        );
+        match next {
+            GameStatus::Anticipation => require!(
+                self.betting_over(global_state)?,
+                QuickBetsErrors::BettingTimeTooSoon
+            ),
+            GameStatus::Resolved | GameStatus::Voided => require!(
+                self.anticipation_over()?,
+                QuickBetsErrors::AnticipationTimeTooSoon
+            ),
+            GameStatus::Closed => {
+                require!(
+                    self.finalized || self.status == GameStatus::Cancelled,
+                    QuickBetsErrors::SettlementNotFinal
+                );
+                require!(self.check_all_bets_claimed(), QuickBetsErrors::BetsNotClaimed)
+            }
+            GameStatus::Betting | GameStatus::Cancelled => {}
+        }
+        msg!("game {} status: {:?} -> {:?}", self.game_id, self.status, next);
+        self.status = next;
+        Ok(())
+    }
+
+    // allowing a margin of error of 1 second, most games should still end at the exact time
+    // with commit-reveal the betting only closes once the reveal window is over
+    pub fn betting_over(&self, global_state: &GlobalState) -> Result<bool> {
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        Ok(self.betting_start + global_state.betting_time + self.reveal_time
+            <= current_time + MARGIN_OF_ERROR)
+    }
+
+    // until the settlement draws the jitter this is the end of the anticipation time, so the
+    // cash-out closes before any scheduled end it could draw
+    pub fn anticipation_over(&self) -> Result<bool> {
+        let current_time = Clock::get()?.unix_timestamp as u64;
+        Ok(self.scheduled_end <= current_time + MARGIN_OF_ERROR)
+    }
+
+    pub fn require_status(&self, status: GameStatus) -> Result<()> {
+        require!(self.status == status, QuickBetsErrors::InvalidGameStatus);
+        Ok(())
+    }
+
+    /// bets can be claimed once the settlement is final, a cancelled game refunds them.
+    pub fn bets_claimable(&self) -> bool {
+        match self.status {
+            GameStatus::Resolved | GameStatus::Voided => self.finalized,
+            GameStatus::Cancelled => true,
+            _ => false,
+        }
+    }
+
+    /// share of the pool sitting on that side, every 100 is 1%.
+    pub fn pool_share(&self, side: u8) -> u64 {
+        (self.side_size(side) as f64 / self.total_pool_size() as f64 * 100.0 * 100.0) as u64
     }
 
     pub fn betting_active(&self, duration: u64) -> Result<bool> {
@@ -174,7 +1218,16 @@ impl Game {
         Ok(true)
     }
 
-    pub fn add_user_bet(&mut self, owner: Pubkey, amount: u64, side: u8) -> Result<u64> {
+    /// placed_at is when the bet was made, its effective amount is weighted by bet_weight.
+    pub fn add_user_bet(
+        &mut self,
+        owner: Pubkey,
+        amount: u64,
+        side: u8,
+        placed_at: u64,
+        betting_time: u64,
+    ) -> Result<u64> {
+        let effective_amount = (amount as f64 * self.bet_weight(placed_at, betting_time)) as u64;
         // look if they have a bet on there already
         for user_bet_slot in self.user_bets.iter_mut() {
             if user_bet_slot.owner == owner {
@@ -182,6 +1235,8 @@ impl Game {
                     return Err(QuickBetsErrors::AlreadyBet.into());
                 }
                 user_bet_slot.amount += amount;
+                user_bet_slot.effective_amount += effective_amount;
+                user_bet_slot.placements += 1;
                 return Ok(user_bet_slot.amount);
             }
         }
@@ -190,14 +1245,95 @@ impl Game {
             if user_bet_slot.owner == Pubkey::default() {
                 user_bet_slot.owner = owner;
                 user_bet_slot.amount = amount;
+                user_bet_slot.effective_amount = effective_amount;
                 user_bet_slot.claimed = false;
                 user_bet_slot.side = side;
+                user_bet_slot.placements = 1;
                 return Ok(user_bet_slot.amount);
             }
         }
         Err(QuickBetsErrors::NoSpaceLeft.into())
     }
 
+    /// What the house pays to take over a bet with the current prices, its payout with the
+    /// current pools weighted by how likely it is to win, minus the haircut.
+    pub fn cash_out_value(
+        &self,
+        user_bet: &UserBet,
+        sol_price: u64,
+        eth_price: u64,
+        global_state: &GlobalState,
+    ) -> u64 {
+        // how far the bet's side is ahead, every 100 is 1%
+        let mut lead = self.lead(sol_price, eth_price);
+        if user_bet.side == 1 {
+            lead = -lead;
+        }
+        let odds_shift = lead * global_state.cash_out_sensitivity as f64 / 100.0 / 100.0 / 100.0;
+        let win_odds = (0.5 + odds_shift).clamp(0.0, 1.0);
+        let payout = user_bet.amount as f64 * self.bet_multiplier(user_bet);
+        let haircut = global_state.cash_out_haircut as f64 / 100.0 / 100.0;
+        (payout * win_odds * (1.0 - haircut)).floor() as u64
+    }
+
+    /// the house takes over the bet, whatever it wins is left in the vault for the house.
+    pub fn mark_cashed_out(&mut self, owner: Pubkey) -> Result<()> {
+        for user_bet_slot in self.user_bets.iter_mut() {
+            if user_bet_slot.owner == owner {
+                user_bet_slot.claimed = true;
+                user_bet_slot.cashed_out = true;
+                return Ok(());
+            }
+        }
+        Err(QuickBetsErrors::NoBetFound.into())
+    }
+
+    /// Locks the quote of the last amount added to a bet on a fixed odds market, the bet gets
+    /// paid at the average of its quotes weighted by their amounts if it wins.
+    pub fn lock_quote(&mut self, owner: Pubkey, amount: u64, multiplier: f64) -> Result<()> {
+        for user_bet_slot in self.user_bets.iter_mut() {
+            if user_bet_slot.owner == owner {
+                let previous_amount = (user_bet_slot.amount - amount) as f64;
+                user_bet_slot.locked_multiplier = (previous_amount
+                    * user_bet_slot.locked_multiplier
+                    + amount as f64 * multiplier)
+                    / user_bet_slot.amount as f64;
+                return Ok(());
+            }
+        }
+        Err(QuickBetsErrors::NoBetFound.into())
+    }
+
+    /// the house match a bet got, given back to the house if the bet is cancelled.
+    pub fn add_house_match(&mut self, owner: Pubkey, amount: u64) -> Result<()> {
+        for user_bet_slot in self.user_bets.iter_mut() {
+            if user_bet_slot.owner == owner {
+                user_bet_slot.house_matched += amount;
+                return Ok(());
+            }
+        }
+        Err(QuickBetsErrors::NoBetFound.into())
+    }
+
+    /// Takes a bet out of the pools along with the house match it got and frees its slot.
+    pub fn remove_user_bet(&mut self, owner: Pubkey) -> Result<UserBet> {
+        let user_bet_slot = self
+            .user_bets
+            .iter_mut()
+            .find(|user_bet_slot| user_bet_slot.owner == owner)
+            .ok_or(QuickBetsErrors::NoBetFound)?;
+        let user_bet = user_bet_slot.clone();
+        *user_bet_slot = UserBet::default();
+
+        // the house matched on the opposite side, bets on a race are never matched
+        self.remove_from_side(user_bet.side, user_bet.amount);
+        if user_bet.house_matched > 0 {
+            self.remove_from_side(1 - user_bet.side, user_bet.house_matched);
+        }
+        self.house_bet_amount -= user_bet.house_matched;
+        Ok(user_bet)
+    }
+
     pub fn get_user_bet(&self, owner: Pubkey) -> Option<UserBet> {
         for user_bet_slot in self.user_bets.iter() {
             if user_bet_slot.owner == owner {
@@ -218,12 +1354,19 @@ impl Game {
     }
 
     pub fn check_all_bets_claimed(&self) -> bool {
+        if self.unfilled_orders_amount > 0 || self.unrevealed_amount() > 0 {
+            return false;
+        }
+        // on a draw or a cancelled game every bet is owed something, unless the pool rolls over
+        let everyone_owed = self.status == GameStatus::Cancelled
+            || self.status == GameStatus::Voided
+            || (self.is_draw() && !self.pool_rolls_over());
         let winning_side = self.get_winner();
 
         for user_bet_slot in self.user_bets.iter() {
             if !user_bet_slot.claimed
                 && user_bet_slot.owner != Pubkey::default()
-                && user_bet_slot.side == winning_side
+                && (everyone_owed || user_bet_slot.side == winning_side)
             {
                 return false;
             }
@@ -231,22 +1374,93 @@ impl Game {
         true
     }
 
-    pub fn get_amount_owed_to_winners(&self, winner_multiplier: f64) -> u64 {
-        let winning_side = self.get_winner();
-        let mut amount = 0;
-        if winning_side == 2 {
-            for user_bet_slot in self.user_bets.iter() {
-                amount += user_bet_slot.amount;
-            }
-            return amount;
-        }
+    pub fn amount_owed_to_users(&self) -> u64 {
+        self.get_amount_owed_to_winners() + self.unfilled_orders_amount + self.unrevealed_amount()
+    }
 
-        for user_bet_slot in self.user_bets.iter() {
-            if user_bet_slot.side == winning_side {
-                amount += (user_bet_slot.amount as f64 * winner_multiplier) as u64;
-            }
-        }
-        amount
+    /// what every bet still in the game is owed, draws follow the draw policy.
+    pub fn get_amount_owed_to_winners(&self) -> u64 {
+        self.user_bets
+            .iter()
+            .filter(|user_bet| user_bet.owner != Pubkey::default() && !user_bet.cashed_out)
+            .map(|user_bet| self.calculate_winning_amount(user_bet))
+            .sum()
+    }
+}
+
+/// What the house has to bet, and on which side, for every bet on either side of these pools to
+/// get at least min_multiplier, sides are sol then eth. A bet gets the pool times its weight over
+/// the effective size of its side, so the lowest weight on a side sets what the pool has to be.
+/// The top up counts in full on the side it's on, u64::MAX means no top up is enough.
+fn top_up_for(
+    side_sizes: [u64; 2],
+    effective_sizes: [u64; 2],
+    min_weights: [f64; 2],
+    min_multiplier: f64,
+) -> (u8, u64) {
+    let pool_size = (side_sizes[0] + side_sizes[1]) as f64;
+    let min_pool = |side: usize, top_up: f64| {
+        min_multiplier * (effective_sizes[side] as f64 + top_up) / min_weights[side]
+    };
+
+    let short_side = if min_pool(0, 0.0) > pool_size {
+        0
+    } else if min_pool(1, 0.0) > pool_size {
+        1
+    } else {
+        return (0, 0);
+    };
+    let house_side = 1 - short_side;
+    let top_up = (min_pool(short_side, 0.0) - pool_size).ceil();
+    // the top up can leave the side it's on short in turn
+    if min_pool(house_side, top_up) > pool_size + top_up {
+        return (house_side as u8, u64::MAX);
+    }
+    (house_side as u8, top_up as u64)
+}
+
+// one per user and game, closed once it's withdrawn
+#[account]
+#[derive(Default)]
+pub struct LimitOrder {
+    pub game: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub side: u8,
+    // the lowest multiplier the order accepts, checked against the final pool sizes
+    pub min_multiplier: f64,
+    pub status: LimitOrderStatus,
+    pub placed_at: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq)]
+pub enum LimitOrderStatus {
+    #[default]
+    Open,
+    // added to the pool as a regular bet
+    Filled,
+    // the odds weren't good enough, the stake can be withdrawn
+    Rejected,
+}
+
+// a stake with a hidden side, one per user and game. Closed once it's revealed or refunded.
+#[account]
+#[derive(Default)]
+pub struct BetCommitment {
+    pub game: Pubkey,
+    pub owner: Pubkey,
+    // sha256 of the side, a salt and the owner
+    pub commitment: [u8; 32],
+    pub amount: u64,
+    pub placed_at: u64,
+}
+
+impl BetCommitment {
+    /// Checks the side and salt against the commitment, the hash is sha256(side, salt, owner).
+    pub fn check_reveal(&self, side: u8, salt: [u8; 32]) -> Result<()> {
+        let hash = hashv(&[&[side], &salt, self.owner.as_ref()]).to_bytes();
+        require!(hash == self.commitment, QuickBetsErrors::InvalidReveal);
+        Ok(())
     }
 }
 
@@ -256,6 +1470,16 @@ pub struct UserBet {
     pub claimed: bool,
     pub side: u8,
     pub owner: Pubkey,
+    // what the house matched against this bet, on fixed odds the cover of its payout
+    pub house_matched: u64,
+    // sold to the house during the anticipation, also marked as claimed
+    pub cashed_out: bool,
+    // what the stake counts for in the pool share once the late bet decay applies
+    pub effective_amount: u64,
+    // what the bet is paid at on a fixed odds market, the average of the quotes it was placed at
+    pub locked_multiplier: f64,
+    // how many bets were added up in this one
+    pub placements: u64,
 }
 
 #[account]
@@ -299,6 +1523,14 @@ impl User {
         }
     }
 
+    /// the volume of a cancelled bet, under the same condition it was added.
+    pub fn remove_volume(&mut self, volume: u64) {
+        let current_time = Clock::get().unwrap().unix_timestamp as u64;
+        if current_time > self.last_spin + 60 * 60 * 24 && !self.claimed {
+            self.volume_24h = self.volume_24h.saturating_sub(volume);
+        }
+    }
+
     pub fn add_bet_record(&mut self, bet_size: u64, win: bool) {
         msg!("adding bet record: {}, {}", bet_size, win);
         self.last_game_bet_size = bet_size;
@@ -335,3 +1567,443 @@ impl User {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user_bet(owner: u8, side: u8, amount: u64) -> UserBet {
+        UserBet {
+            amount,
+            side,
+            owner: Pubkey::new_from_array([owner; 32]),
+            effective_amount: amount,
+            ..Default::default()
+        }
+    }
+
+    /// a duel between two bettors, sol and eth both starting at 100
+    fn duel(sol_bet: u64, eth_bet: u64) -> Game {
+        let mut game = Game {
+            initial_sol_price: 100_000,
+            initial_eth_price: 100_000,
+            final_sol_price: 100_000,
+            final_eth_price: 100_000,
+            status: GameStatus::Resolved,
+            ..Default::default()
+        };
+        game.user_bets[0] = user_bet(1, 0, sol_bet);
+        game.user_bets[1] = user_bet(2, 1, eth_bet);
+        game.sol_bet_size = sol_bet;
+        game.eth_bet_size = eth_bet;
+        game
+    }
+
+    #[test]
+    fn voided_game_with_handicap_refunds_both_sides() {
+        let mut game = duel(100, 300);
+        game.market.draw_band = 10;
+        // flat prices, the handicap alone puts eth ahead
+        game.market.handicap = 50;
+        game.status = GameStatus::Voided;
+        assert_eq!(game.get_winner(), 1);
+        assert_eq!(game.calculate_winning_amount(&game.user_bets[0]), 100);
+        assert_eq!(game.calculate_winning_amount(&game.user_bets[1]), 300);
+    }
+
+    #[test]
+    fn voided_bucket_market_refunds_every_bucket() {
+        let mut game = duel(0, 0);
+        game.market.market_type = MarketType::Buckets;
+        game.market.bucket_count = 3;
+        game.market.bucket_edges[..2].copy_from_slice(&[-20, 20]);
+        game.final_sol_price = 101_000;
+        game.status = GameStatus::Voided;
+        for (i, side) in [0, 1, 2].iter().enumerate() {
+            game.user_bets[i] = user_bet(i as u8 + 1, *side, 100 * (i as u64 + 1));
+            game.outcome_pools[*side as usize] = 100 * (i as u64 + 1);
+        }
+        assert_eq!(game.get_winner(), 2);
+        for user_bet in game.user_bets[..3].iter() {
+            assert_eq!(game.calculate_winning_amount(user_bet), user_bet.amount);
+        }
+    }
+
+    #[test]
+    fn top_up_covers_late_bets_at_their_weight() {
+        let mut game = duel(100, 100);
+        game.min_multiplier = 1.75;
+        // a late bet on sol counting for 90% of its stake
+        game.user_bets[2] = UserBet {
+            effective_amount: 90,
+            ..user_bet(3, 0, 100)
+        };
+        game.sol_bet_size += 100;
+
+        let (house_side, top_up) = game.required_top_up();
+        assert_eq!((house_side, top_up), (1, 70));
+        game.add_to_side(house_side, top_up);
+        assert!(game.bet_multiplier(&game.user_bets[2]) >= 1.75);
+        assert!(game.bet_multiplier(&game.user_bets[1]) >= 1.75);
+        assert_eq!(game.required_top_up(), (0, 0));
+    }
+
+    #[test]
+    fn top_ups_are_reserved_across_games() {
+        let mut global_state = GlobalState::default();
+        let mut first = duel(300, 100);
+        let mut second = duel(300, 100);
+        for game in [&mut first, &mut second] {
+            game.min_multiplier = 1.75;
+            game.max_house_bet_size = 1_000;
+        }
+        // each game needs the house to put 125 on eth
+        assert_eq!(first.required_top_up(), (1, 125));
+
+        global_state.reserve_top_up(&mut first, 200).unwrap();
+        assert_eq!(global_state.reserved_top_ups, 125);
+        assert!(global_state.reserve_top_up(&mut second, 200).is_err());
+        // reserving again for the same game doesn't count it twice
+        global_state.reserve_top_up(&mut first, 200).unwrap();
+        assert_eq!(global_state.reserved_top_ups, 125);
+
+        global_state.release_top_up(&mut first);
+        global_state.reserve_top_up(&mut second, 200).unwrap();
+        assert_eq!(global_state.reserved_top_ups, 125);
+    }
+
+    #[test]
+    fn fixed_odds_quote_shrinks_with_exposure_and_is_locked_per_bet() {
+        let mut game = duel(0, 0);
+        game.user_bets = Default::default();
+        game.market.payout_mode = PayoutMode::FixedOdds;
+        game.market.fixed_multiplier = 2.0;
+        game.market.fixed_odds_skew = 5_000;
+        game.max_house_bet_size = 1_000;
+        let owner = Pubkey::new_from_array([1; 32]);
+
+        assert_eq!(game.quote_multiplier(0), 2.0);
+        game.add_user_bet(owner, 500, 0, 0, 0).unwrap();
+        game.add_house_match(owner, game.fixed_odds_cover(500, 2.0)).unwrap();
+        game.lock_quote(owner, 500, 2.0).unwrap();
+
+        // half the capacity covers sol, its quote loses a quarter of its margin
+        assert_eq!(game.quote_multiplier(0), 1.75);
+        assert_eq!(game.quote_multiplier(1), 2.0);
+        game.add_user_bet(owner, 500, 0, 0, 0).unwrap();
+        game.lock_quote(owner, 500, 1.75).unwrap();
+        let user_bet = game.get_user_bet(owner).unwrap();
+        assert_eq!(user_bet.locked_multiplier, 1.875);
+        game.final_sol_price = 101_000;
+        assert_eq!(game.calculate_winning_amount(&user_bet), 1_875);
+    }
+
+    const STATUSES: [GameStatus; 6] = [
+        GameStatus::Betting,
+        GameStatus::Anticipation,
+        GameStatus::Resolved,
+        GameStatus::Voided,
+        GameStatus::Closed,
+        GameStatus::Cancelled,
+    ];
+
+    #[test]
+    fn transition_table() {
+        use GameStatus::*;
+        let allowed = [
+            (Betting, Anticipation),
+            (Betting, Cancelled),
+            (Anticipation, Resolved),
+            (Anticipation, Voided),
+            (Anticipation, Cancelled),
+            (Resolved, Closed),
+            (Voided, Closed),
+            (Cancelled, Closed),
+        ];
+        for from in STATUSES.iter() {
+            for to in STATUSES.iter() {
+                assert_eq!(
+                    from.can_transition_to(to),
+                    allowed.contains(&(from.clone(), to.clone())),
+                    "{:?} -> {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn winning_amount_for_every_status() {
+        let mut game = duel(100, 300);
+        // sol up 1%, eth flat
+        game.final_sol_price = 101_000;
+        for status in STATUSES.iter() {
+            game.status = status.clone();
+            let expected = match status {
+                GameStatus::Voided | GameStatus::Cancelled => (100, 300),
+                // the prices decide, sol wins the whole pool
+                _ => (400, 0),
+            };
+            assert_eq!(
+                (
+                    game.calculate_winning_amount(&game.user_bets[0]),
+                    game.calculate_winning_amount(&game.user_bets[1])
+                ),
+                expected,
+                "{:?}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn bets_claimable_by_status() {
+        let mut game = duel(100, 300);
+        for finalized in [false, true] {
+            game.finalized = finalized;
+            for status in STATUSES.iter() {
+                game.status = status.clone();
+                let expected = match status {
+                    GameStatus::Resolved | GameStatus::Voided => finalized,
+                    GameStatus::Cancelled => true,
+                    _ => false,
+                };
+                assert_eq!(game.bets_claimable(), expected, "{:?}", status);
+            }
+        }
+    }
+
+    #[test]
+    fn global_state_fits_its_account() {
+        // the discriminator, then the state
+        let size = 8 + GlobalState::default().try_to_vec().unwrap().len();
+        assert!(size <= crate::consts::GLOBAL_STATE_SIZE, "{}", size);
+    }
+
+    #[test]
+    fn top_up_goes_against_the_short_side() {
+        let top_up = |sizes: [u64; 2]| top_up_for(sizes, sizes, [1.0, 1.0], 1.75);
+        assert_eq!(top_up([100, 100]), (0, 0));
+        assert_eq!(top_up([300, 100]), (1, 125));
+        assert_eq!(top_up([100, 300]), (0, 125));
+        assert_eq!(top_up([0, 0]), (0, 0));
+        // late bets on sol counting for half their stake, topping up eth only makes it short
+        assert_eq!(
+            top_up_for([200, 100], [150, 100], [0.5, 1.0], 1.75),
+            (1, u64::MAX)
+        );
+    }
+
+    fn limit_order(owner: u8, side: u8, amount: u64, min_multiplier: f64) -> LimitOrder {
+        LimitOrder {
+            owner: Pubkey::new_from_array([owner; 32]),
+            amount,
+            side,
+            min_multiplier,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn limit_orders_fill_only_at_their_odds() {
+        let global_state = GlobalState {
+            max_user_bet: 1_000,
+            ..Default::default()
+        };
+        let mut game = duel(100, 100);
+        game.min_multiplier = 1.75;
+        game.max_house_bet_size = 1_000;
+        // eth gets 263 / 150 with the house top up, sol would only get 350 / 200
+        let mut orders = [limit_order(3, 0, 100, 1.9), limit_order(4, 1, 50, 1.5)];
+        game.open_order_count = 2;
+        game.unfilled_orders_amount = 150;
+
+        let mut without_house = game.clone();
+        let mut orders_without_house = orders.clone();
+        game.fill_limit_orders(&mut orders, &global_state, 1_000);
+        assert_eq!(orders[0].status, LimitOrderStatus::Rejected);
+        assert_eq!(orders[1].status, LimitOrderStatus::Filled);
+        assert_eq!((game.sol_bet_size, game.eth_bet_size), (100, 150));
+        assert_eq!(game.get_user_bet(orders[1].owner).unwrap().amount, 50);
+        assert_eq!(game.required_top_up(), (0, 13));
+        assert_eq!((game.open_order_count, game.unfilled_orders_amount), (0, 100));
+
+        // the rejected order gets its stake back, the filled one is only closed
+        game.status = GameStatus::Anticipation;
+        assert_eq!(game.withdraw_limit_order(&orders[0]).unwrap(), 100);
+        assert_eq!(game.withdraw_limit_order(&orders[1]).unwrap(), 0);
+        assert_eq!(game.unfilled_orders_amount, 0);
+
+        // the house can't put in the top up the eth order needs
+        without_house.fill_limit_orders(&mut orders_without_house, &global_state, 0);
+        assert_eq!(orders_without_house[1].status, LimitOrderStatus::Rejected);
+        assert_eq!((without_house.sol_bet_size, without_house.eth_bet_size), (100, 100));
+    }
+
+    #[test]
+    fn commitments_refund_once_the_reveal_is_over() {
+        let mut game = Game {
+            committed_amount: 300,
+            ..Default::default()
+        };
+        // still in the betting or the reveal
+        assert!(game.refund_commitment(100).is_err());
+
+        let mut cancelled = game.clone();
+        cancelled.status = GameStatus::Cancelled;
+        assert_eq!(cancelled.refund_commitment(100).unwrap(), 100);
+        assert_eq!(cancelled.unrevealed_amount(), 200);
+
+        game.status = GameStatus::Anticipation;
+        game.anticipating_start = 1;
+        let mut forfeiting = game.clone();
+        forfeiting.forfeit_unrevealed = true;
+        assert_eq!(forfeiting.unrevealed_amount(), 0);
+        assert_eq!(forfeiting.refund_commitment(100).unwrap(), 0);
+
+        assert_eq!(game.refund_commitment(100).unwrap(), 100);
+        assert_eq!(game.unrevealed_amount(), 200);
+        assert!(!game.check_all_bets_claimed());
+    }
+
+    #[test]
+    fn reveal_matches_the_commitment() {
+        let owner = Pubkey::new_from_array([1; 32]);
+        let salt = [7; 32];
+        let commitment = BetCommitment {
+            owner,
+            commitment: hashv(&[&[1], &salt, owner.as_ref()]).to_bytes(),
+            ..Default::default()
+        };
+        assert!(commitment.check_reveal(1, salt).is_ok());
+        assert!(commitment.check_reveal(0, salt).is_err());
+        assert!(commitment.check_reveal(1, [8; 32]).is_err());
+    }
+
+    #[test]
+    fn draw_band_and_draw_policies() {
+        let mut game = duel(100, 300);
+        game.market.draw_band = 50;
+        let winner = |game: &mut Game, sol_price: u64, eth_price: u64| {
+            game.final_sol_price = sol_price;
+            game.final_eth_price = eth_price;
+            game.get_winner()
+        };
+        assert_eq!(winner(&mut game, 100_600, 100_000), 0);
+        assert_eq!(winner(&mut game, 100_000, 100_600), 1);
+        assert_eq!(winner(&mut game, 100_400, 100_000), 2);
+        assert_eq!(winner(&mut game, 100_000, 99_500), 2);
+
+        let payouts = |game: &Game| {
+            (
+                game.calculate_winning_amount(&game.user_bets[0]),
+                game.calculate_winning_amount(&game.user_bets[1]),
+            )
+        };
+        assert_eq!(payouts(&game), (100, 300));
+        game.market.draw_policy = DrawPolicy::HouseFee;
+        game.market.draw_fee = 100;
+        assert_eq!(payouts(&game), (99, 297));
+        game.market.draw_policy = DrawPolicy::RollOver;
+        assert_eq!(payouts(&game), (0, 0));
+        assert!(game.pool_rolls_over());
+    }
+
+    #[test]
+    fn handicap_shifts_the_winner() {
+        let mut game = duel(100, 300);
+        game.market.draw_band = 10;
+        // sol has to outperform eth by 1%
+        game.market.handicap = 100;
+        for (sol_price, winner) in [(100_500, 1), (101_050, 2), (101_500, 0)] {
+            game.final_sol_price = sol_price;
+            assert_eq!(game.get_winner(), winner, "{}", sol_price);
+        }
+        // eth has to outperform sol by 1%, flat prices are a win for sol
+        game.market.handicap = -100;
+        game.final_sol_price = 100_000;
+        assert_eq!(game.get_winner(), 0);
+        game.final_eth_price = 101_000;
+        assert_eq!(game.get_winner(), 2);
+    }
+
+    #[test]
+    fn race_winner_is_the_best_performer() {
+        let mut game = duel(0, 0);
+        game.user_bets = Default::default();
+        game.market.market_type = MarketType::Race;
+        game.market.race_feed_count = 3;
+        game.market.draw_band = 20;
+        game.race_initial_prices[..3].copy_from_slice(&[100_000, 200_000, 300_000]);
+        for side in 0..3 {
+            game.user_bets[side as usize] = user_bet(side + 1, side, 100);
+            game.add_to_side(side, 100);
+        }
+
+        // +10%, +5% and flat
+        game.race_final_prices[..3].copy_from_slice(&[110_000, 210_000, 300_000]);
+        assert_eq!(game.get_winner(), 0);
+        assert_eq!(game.calculate_winning_amount(&game.user_bets[0]), 300);
+        assert_eq!(game.calculate_winning_amount(&game.user_bets[1]), 0);
+        // the third one gains the most
+        game.race_final_prices[2] = 340_000;
+        assert_eq!(game.get_winner(), 2);
+        // the second one within 0.2% of the first is a draw
+        game.race_final_prices[..3].copy_from_slice(&[110_000, 219_800, 300_000]);
+        assert_eq!(game.get_winner(), game.draw_outcome());
+        assert_eq!(game.draw_outcome(), 3);
+    }
+
+    #[test]
+    fn bucket_winner_by_outperformance() {
+        let mut game = duel(0, 0);
+        game.user_bets = Default::default();
+        game.market.market_type = MarketType::Buckets;
+        game.market.bucket_count = 3;
+        game.market.bucket_edges[..2].copy_from_slice(&[-20, 20]);
+        game.market.draw_band = 5;
+        // how much sol outperforms eth, every 100 is 1%
+        let cases = [(99_500, 0), (100_000, 1), (100_500, 2), (100_180, 3), (99_760, 3)];
+        for (sol_price, bucket) in cases {
+            game.final_sol_price = sol_price;
+            assert_eq!(game.get_winner(), bucket, "{}", sol_price);
+        }
+        // without a band there's no draw, a lead right on an edge is in the bucket above it
+        game.market.draw_band = 0;
+        game.final_sol_price = 100_200;
+        assert_eq!(game.get_winner(), 2);
+        game.final_sol_price = 99_800;
+        assert_eq!(game.get_winner(), 1);
+        game.final_sol_price = 99_790;
+        assert_eq!(game.get_winner(), 0);
+    }
+
+    #[test]
+    fn emergency_refund_time_comes_from_the_game() {
+        let mut game = duel(0, 0);
+        game.betting_start = 1_000;
+        game.reveal_time = 20;
+        game.scheduled_end = 0;
+        assert_eq!(game.emergency_refund_time(), 1_020 + EMERGENCY_REFUND_TIMEOUT);
+        game.scheduled_end = 1_100;
+        assert_eq!(game.emergency_refund_time(), 1_100 + EMERGENCY_REFUND_TIMEOUT);
+    }
+
+    #[test]
+    fn game_records_only_clear_once_every_game_is_closed() {
+        let mut global_state = GlobalState::default();
+        global_state.game_records[0].game_address = Pubkey::new_unique();
+        global_state.game_records[0].status = GameStatus::Closed;
+        global_state.game_records[1].game_address = Pubkey::new_unique();
+        global_state.game_records[1].status = GameStatus::Resolved;
+        assert!(global_state.clear_game_records().is_err());
+
+        global_state.game_records[1].status = GameStatus::Cancelled;
+        assert!(global_state.clear_game_records().is_ok());
+        assert!(global_state
+            .game_records
+            .iter()
+            .all(|game| game.game_address == Pubkey::default()));
+    }
+}
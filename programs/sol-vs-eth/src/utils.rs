@@ -1,6 +1,262 @@
+use std::str::FromStr;
+
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
 use anchor_spl::token;
+use anchor_lang::solana_program::sysvar;
 use pyth_sdk_solana::load_price_feed_from_account_info;
+use pyth_sdk_solana::state::{load_price_account, PriceAccount, PriceStatus};
+
+use crate::consts::{ETH_ORACLE, PRICE_UPDATE_DISCRIMINATOR, PYTH_RECEIVER, SOL_ORACLE};
+use crate::events::CrankRewardPaid;
+use crate::quick_bets_errors::QuickBetsErrors;
+use crate::state::{GlobalState, MarketConfig, MarketType};
+
+/// initial and final prices of a game, only from the sol and eth feeds.
+pub fn get_game_prices(sol_feed: &AccountInfo, eth_feed: &AccountInfo) -> Result<(u64, u64)> {
+    if Pubkey::from_str(SOL_ORACLE).unwrap() != *sol_feed.key {
+        return Err(QuickBetsErrors::InvalidOracle.into());
+    }
+
+    if Pubkey::from_str(ETH_ORACLE).unwrap() != *eth_feed.key {
+        return Err(QuickBetsErrors::InvalidOracle.into());
+    }
+
+    let sol_price = get_price_from_pyth(sol_feed.clone())?;
+    let eth_price = get_price_from_pyth(eth_feed.clone())?;
+    msg!("Sol price: {}", sol_price);
+    msg!("Eth price: {}", eth_price);
+    Ok((sol_price, eth_price))
+}
+
+#[derive(Default)]
+pub struct SettlementPrices {
+    pub sol_price: u64,
+    pub sol_publish_time: i64,
+    pub eth_price: u64,
+    pub eth_publish_time: i64,
+}
+
+/// Final prices of a game, from the feed observations nearest to its scheduled end. They are only
+/// accepted if they were published within tolerance seconds of it, so anyone resolving the game
+/// ends up with the same prices.
+/// A feed only keeps its last two aggregates, so the crank has to settle the game within about
+/// tolerance seconds after the scheduled end, while one of them is still near it. Past that the
+/// settlement fails until the game gets voided, 15 seconds after its scheduled end.
+pub fn get_settlement_prices(
+    sol_feed: &AccountInfo,
+    eth_feed: &AccountInfo,
+    scheduled_end: u64,
+    tolerance: u64,
+) -> Result<SettlementPrices> {
+    if Pubkey::from_str(SOL_ORACLE).unwrap() != *sol_feed.key {
+        return Err(QuickBetsErrors::InvalidOracle.into());
+    }
+
+    if Pubkey::from_str(ETH_ORACLE).unwrap() != *eth_feed.key {
+        return Err(QuickBetsErrors::InvalidOracle.into());
+    }
+
+    let (sol_price, sol_publish_time) = get_price_nearest(sol_feed, scheduled_end as i64)?;
+    let (eth_price, eth_publish_time) = get_price_nearest(eth_feed, scheduled_end as i64)?;
+    msg!("Sol price: {} published at {}", sol_price, sol_publish_time);
+    msg!("Eth price: {} published at {}", eth_price, eth_publish_time);
+    require!(
+        sol_publish_time.abs_diff(scheduled_end as i64) <= tolerance
+            && eth_publish_time.abs_diff(scheduled_end as i64) <= tolerance,
+        QuickBetsErrors::NoPriceNearSettlement
+    );
+    Ok(SettlementPrices {
+        sol_price,
+        sol_publish_time,
+        eth_price,
+        eth_publish_time,
+    })
+}
+
+/// Prices of the feeds of a race by outcome, the feeds are passed in the order of the outcomes.
+/// Nothing is read for other market types.
+pub fn get_race_prices(feeds: &[AccountInfo], market: &MarketConfig) -> Result<[u64; 8]> {
+    let mut prices = [0; 8];
+    for (side, feed) in race_feeds(feeds, market)?.iter().enumerate() {
+        let price_feed = load_price_feed_from_account_info(feed)
+            .map_err(|_| error!(QuickBetsErrors::InvalidOracle))?;
+        let price = price_feed.get_price_unchecked().price;
+        msg!("Outcome {} price: {}", side, price);
+        // the changes of the outcomes are relative to it
+        require!(price > 0, QuickBetsErrors::InvalidPrice);
+        prices[side] = price as u64;
+    }
+    Ok(prices)
+}
+
+/// Final prices of a race by outcome, held to the same tolerance around the scheduled end as the
+/// sol and eth prices.
+pub fn get_race_settlement_prices(
+    feeds: &[AccountInfo],
+    market: &MarketConfig,
+    scheduled_end: u64,
+    tolerance: u64,
+) -> Result<[u64; 8]> {
+    let mut prices = [0; 8];
+    for (side, feed) in race_feeds(feeds, market)?.iter().enumerate() {
+        let (price, publish_time) = get_price_nearest(feed, scheduled_end as i64)?;
+        msg!("Outcome {} price: {} published at {}", side, price, publish_time);
+        require!(
+            publish_time.abs_diff(scheduled_end as i64) <= tolerance,
+            QuickBetsErrors::NoPriceNearSettlement
+        );
+        prices[side] = price;
+    }
+    Ok(prices)
+}
+
+/// checks the passed feeds against the ones of the race.
+fn race_feeds<'a, 'info>(
+    feeds: &'a [AccountInfo<'info>],
+    market: &MarketConfig,
+) -> Result<&'a [AccountInfo<'info>]> {
+    if market.market_type != MarketType::Race {
+        return Ok(&[]);
+    }
+    let feed_count = market.race_feed_count as usize;
+    require!(feeds.len() >= feed_count, QuickBetsErrors::InvalidOracle);
+    for (feed, expected) in feeds.iter().zip(market.race_feeds[..feed_count].iter()) {
+        require_keys_eq!(*feed.key, *expected, QuickBetsErrors::InvalidOracle);
+    }
+    Ok(&feeds[..feed_count])
+}
+
+/// A pyth price account keeps its current aggregate and the previous one,
+/// returns whichever was published nearest to target_time along with its publish time.
+pub fn get_price_nearest(oracle_address: &AccountInfo, target_time: i64) -> Result<(u64, i64)> {
+    let data = oracle_address.try_borrow_data()?;
+    let price_account =
+        load_price_account(&data).map_err(|_| error!(QuickBetsErrors::InvalidOracle))?;
+    nearest_price(price_account, target_time)
+}
+
+/// The current aggregate is only a candidate while it's trading. The previous one is the last
+/// aggregate that was trading, pyth only rolls those into it, and it's only a candidate once there
+/// was one. Neither is used with a price that isn't positive.
+fn nearest_price(price_account: &PriceAccount, target_time: i64) -> Result<(u64, i64)> {
+    let current = (price_account.agg.status == PriceStatus::Trading
+        && price_account.agg.price > 0)
+        .then_some((price_account.agg.price, price_account.timestamp));
+    let previous = (price_account.prev_slot > 0 && price_account.prev_price > 0)
+        .then_some((price_account.prev_price, price_account.prev_timestamp));
+    // the current one wins a tie
+    let (price, publish_time) = [current, previous]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(_, publish_time)| publish_time.abs_diff(target_time))
+        .ok_or(QuickBetsErrors::InvalidPrice)?;
+    Ok((price as u64, publish_time))
+}
+
+/// The price of a pyth price update posted by the receiver program for that feed id, in the
+/// exponent of the push feed, along with its publish time. Only fully verified updates are
+/// accepted, they can be posted for any time so they prove the price at the scheduled end.
+pub fn get_price_update(
+    price_update: &AccountInfo,
+    push_feed: &AccountInfo,
+    feed_id: &[u8; 32],
+) -> Result<(u64, i64)> {
+    require_keys_eq!(
+        *price_update.owner,
+        Pubkey::from_str(PYTH_RECEIVER).unwrap(),
+        QuickBetsErrors::InvalidPriceUpdate
+    );
+    let (price, exponent, publish_time) =
+        parse_price_update(&price_update.try_borrow_data()?, feed_id)?;
+
+    let feed_data = push_feed.try_borrow_data()?;
+    let feed_exponent = load_price_account(&feed_data)
+        .map_err(|_| error!(QuickBetsErrors::InvalidOracle))?
+        .expo;
+    let price = if exponent >= feed_exponent {
+        10_i128
+            .checked_pow((exponent - feed_exponent) as u32)
+            .and_then(|scale| (price as i128).checked_mul(scale))
+    } else {
+        10_i128
+            .checked_pow((feed_exponent - exponent) as u32)
+            .map(|scale| price as i128 / scale)
+    };
+    let price = price
+        .and_then(|price| u64::try_from(price).ok())
+        .filter(|price| *price > 0)
+        .ok_or(QuickBetsErrors::InvalidPriceUpdate)?;
+    Ok((price, publish_time))
+}
+
+/// Reads the price, exponent and publish time out of the data of a PriceUpdateV2 account:
+/// discriminator, write authority, verification level, then the price message.
+pub fn parse_price_update(data: &[u8], feed_id: &[u8; 32]) -> Result<(i64, i32, i64)> {
+    // verification level 1 is Full, 0 is Partial and followed by its number of signatures
+    let message = 41;
+    require!(
+        data.len() >= message + 60
+            && data[..8] == PRICE_UPDATE_DISCRIMINATOR
+            && data[40] == 1
+            && data[message..message + 32] == feed_id[..],
+        QuickBetsErrors::InvalidPriceUpdate
+    );
+    let price = i64::from_le_bytes(data[message + 32..message + 40].try_into().unwrap());
+    let exponent = i32::from_le_bytes(data[message + 48..message + 52].try_into().unwrap());
+    let publish_time = i64::from_le_bytes(data[message + 52..message + 60].try_into().unwrap());
+    Ok((price, exponent, publish_time))
+}
+
+/// A pseudo random number up to max_jitter, from the hash of the first slot at or after
+/// jitter_slot. SlotHashes only holds past slots, so a cranker could simulate the latest one and
+/// pick when to send. The slot is committed when the anticipation starts instead, once the bets
+/// are closed, and the jitter is drawn when the game is settled: the hash is unknown while
+/// anyone can still bet and the same whoever settles the game.
+pub fn get_jitter(slot_hashes: &AccountInfo, max_jitter: u64, jitter_slot: u64) -> Result<u64> {
+    require_keys_eq!(
+        *slot_hashes.key,
+        sysvar::slot_hashes::ID,
+        QuickBetsErrors::InvalidSlotHashes
+    );
+    if max_jitter == 0 {
+        return Ok(0);
+    }
+    jitter_from_slot_hashes(&slot_hashes.try_borrow_data()?, max_jitter, jitter_slot)
+}
+
+/// Reads the SlotHashes data: the entry count, then (slot, hash) entries from the latest slot.
+/// Fails while jitter_slot isn't reached, and there's no jitter once it's older than every
+/// entry since the hash can't be found anymore.
+pub fn jitter_from_slot_hashes(data: &[u8], max_jitter: u64, jitter_slot: u64) -> Result<u64> {
+    require!(data.len() >= 8, QuickBetsErrors::InvalidSlotHashes);
+    let entry_count = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+    require!(
+        entry_count > 0 && data.len() >= 8 + entry_count * 40,
+        QuickBetsErrors::InvalidSlotHashes
+    );
+    let entries: Vec<(u64, &[u8])> = data[8..8 + entry_count * 40]
+        .chunks(40)
+        .map(|entry| (u64::from_le_bytes(entry[..8].try_into().unwrap()), &entry[8..]))
+        .collect();
+
+    require!(
+        entries[0].0 >= jitter_slot,
+        QuickBetsErrors::JitterSlotPending
+    );
+    if entries[entries.len() - 1].0 > jitter_slot {
+        msg!("jitter slot {} isn't in the slot hashes anymore", jitter_slot);
+        return Ok(0);
+    }
+    // skipped slots have no entry, the first one after it is used then
+    let (_, hash) = entries
+        .iter()
+        .rev()
+        .find(|(slot, _)| *slot >= jitter_slot)
+        .unwrap();
+    let seed = u64::from_le_bytes(hash[..8].try_into().unwrap());
+    Ok(seed % (max_jitter + 1))
+}
 
 pub fn get_price_from_pyth(oracle_address: AccountInfo) -> Result<u64> {
     let price_feed = load_price_feed_from_account_info(&oracle_address).unwrap();
@@ -15,6 +271,37 @@ pub fn get_price_from_pyth(oracle_address: AccountInfo) -> Result<u64> {
     Ok(price_lots as u64)
 }
 
+/// pays the caller of a crank step out of the fees set aside in the house wallet.
+pub fn pay_crank_reward<'a>(
+    global_state: &mut GlobalState,
+    game: Pubkey,
+    house_wallet: AccountInfo<'a>,
+    receiver: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    token_program: AccountInfo<'a>,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let reward = global_state.take_crank_reward();
+    if reward == 0 {
+        return Ok(());
+    }
+    let cranker = receiver.key();
+    transfer_tokens(
+        house_wallet,
+        receiver,
+        authority,
+        token_program,
+        reward,
+        Some(seeds),
+    )?;
+    emit!(CrankRewardPaid {
+        game,
+        cranker,
+        amount: reward,
+    });
+    Ok(())
+}
+
 pub fn transfer_tokens<'a>(
     from: AccountInfo<'a>,
     to: AccountInfo<'a>,
@@ -38,3 +325,189 @@ pub fn transfer_tokens<'a>(
     token::transfer(cpi_ctx, amount)?;
     Ok(())
 }
+
+/// Creates a PDA owned by owner the way anchor's init does. Anyone can send lamports to the
+/// address beforehand, create_account would fail on it, so an account that already has lamports
+/// is only topped up to the rent exemption, then allocated and assigned.
+pub fn create_pda_account<'a>(
+    payer: AccountInfo<'a>,
+    account: AccountInfo<'a>,
+    system_program: AccountInfo<'a>,
+    space: usize,
+    owner: &Pubkey,
+    seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    if account.lamports() == 0 {
+        return system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program,
+                system_program::CreateAccount {
+                    from: payer,
+                    to: account,
+                },
+                seeds,
+            ),
+            rent,
+            space as u64,
+            owner,
+        );
+    }
+
+    let missing_rent = rent.saturating_sub(account.lamports());
+    if missing_rent > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: payer,
+                    to: account.clone(),
+                },
+            ),
+            missing_rent,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            system_program.clone(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            seeds,
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            system_program,
+            system_program::Assign {
+                account_to_assign: account,
+            },
+            seeds,
+        ),
+        owner,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::{ETH_FEED_ID, SOL_FEED_ID};
+    use crate::state::Game;
+
+    fn price_update(
+        verification: &[u8],
+        feed_id: &[u8; 32],
+        price: i64,
+        publish_time: i64,
+    ) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[7; 32]);
+        data.extend_from_slice(verification);
+        data.extend_from_slice(feed_id);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&1_000_u64.to_le_bytes());
+        data.extend_from_slice(&(-8_i32).to_le_bytes());
+        data.extend_from_slice(&publish_time.to_le_bytes());
+        data.extend_from_slice(&[0; 32]);
+        data
+    }
+
+    #[test]
+    fn challenge_with_a_price_update_closer_to_the_scheduled_end() {
+        let mut game = Game {
+            scheduled_end: 1_000,
+            final_sol_price: 15_000_000_000,
+            sol_publish_time: 1_004,
+            final_eth_price: 300_000_000_000,
+            eth_publish_time: 1_000,
+            ..Default::default()
+        };
+        let data = price_update(&[1], &SOL_FEED_ID, 14_900_000_000, 1_001);
+        let (price, exponent, publish_time) = parse_price_update(&data, &SOL_FEED_ID).unwrap();
+        assert_eq!((price, exponent, publish_time), (14_900_000_000, -8, 1_001));
+
+        assert!(game.apply_challenge(Some((price as u64, publish_time)), None));
+        assert_eq!(game.final_sol_price, 14_900_000_000);
+        assert_eq!(game.sol_publish_time, 1_001);
+        assert_eq!(game.final_eth_price, 300_000_000_000);
+        // the same observation again isn't any closer
+        assert!(!game.apply_challenge(Some((price as u64, publish_time)), None));
+    }
+
+    #[test]
+    fn price_update_has_to_be_fully_verified_for_the_feed() {
+        let partial = price_update(&[0, 5], &SOL_FEED_ID, 14_900_000_000, 1_001);
+        assert!(parse_price_update(&partial, &SOL_FEED_ID).is_err());
+        let other_feed = price_update(&[1], &ETH_FEED_ID, 14_900_000_000, 1_001);
+        assert!(parse_price_update(&other_feed, &SOL_FEED_ID).is_err());
+    }
+
+    #[test]
+    fn nearest_price_only_from_trading_positive_aggregates() {
+        let mut price_account = PriceAccount {
+            timestamp: 1_002,
+            prev_slot: 10,
+            prev_price: 14_900_000_000,
+            prev_timestamp: 999,
+            ..Default::default()
+        };
+        price_account.agg.price = 15_000_000_000;
+        price_account.agg.status = PriceStatus::Trading;
+        assert_eq!(
+            nearest_price(&price_account, 1_000).unwrap(),
+            (14_900_000_000, 999)
+        );
+        assert_eq!(
+            nearest_price(&price_account, 1_001).unwrap(),
+            (15_000_000_000, 1_002)
+        );
+
+        // the current aggregate is skipped unless it's trading with a positive price
+        price_account.agg.status = PriceStatus::Unknown;
+        assert_eq!(
+            nearest_price(&price_account, 1_002).unwrap(),
+            (14_900_000_000, 999)
+        );
+        price_account.agg.status = PriceStatus::Trading;
+        price_account.agg.price = 0;
+        assert_eq!(
+            nearest_price(&price_account, 1_002).unwrap(),
+            (14_900_000_000, 999)
+        );
+
+        // so is a previous one that isn't positive or never was
+        price_account.prev_price = -1;
+        assert!(nearest_price(&price_account, 1_000).is_err());
+        price_account.agg.price = 15_000_000_000;
+        price_account.prev_price = 14_900_000_000;
+        price_account.prev_slot = 0;
+        assert_eq!(
+            nearest_price(&price_account, 1_000).unwrap(),
+            (15_000_000_000, 1_002)
+        );
+    }
+
+    fn slot_hashes(slots: &[u64]) -> Vec<u8> {
+        let mut data = (slots.len() as u64).to_le_bytes().to_vec();
+        for slot in slots {
+            data.extend_from_slice(&slot.to_le_bytes());
+            data.extend_from_slice(&[*slot as u8; 32]);
+        }
+        data
+    }
+
+    #[test]
+    fn jitter_comes_from_the_committed_slot() {
+        // slot 103 was skipped, the jitter comes from 104 whichever slot the crank lands in
+        let seed = u64::from_le_bytes([104; 8]);
+        let data = slot_hashes(&[106, 105, 104, 102, 101]);
+        assert_eq!(jitter_from_slot_hashes(&data, 10, 103).unwrap(), seed % 11);
+        let data = slot_hashes(&[110, 109, 108, 107, 106, 105, 104, 102]);
+        assert_eq!(jitter_from_slot_hashes(&data, 10, 103).unwrap(), seed % 11);
+
+        // not reached yet, and too old to be found
+        assert!(jitter_from_slot_hashes(&data, 10, 111).is_err());
+        assert_eq!(jitter_from_slot_hashes(&data, 10, 101).unwrap(), 0);
+    }
+}
@@ -1,13 +1,17 @@
 import * as anchor from "@coral-xyz/anchor";
 import { BN, Program } from "@coral-xyz/anchor";
 import { MulberryQuickBets } from "../target/types/mulberry_quick_bets";
-import { Keypair, PublicKey } from "@solana/web3.js";
-import { TOKEN_PROGRAM_ID, createAccount, createMint, mintTo } from "@solana/spl-token";
+import { Keypair, PublicKey, SYSVAR_SLOT_HASHES_PUBKEY } from "@solana/web3.js";
+import { TOKEN_PROGRAM_ID, createAccount, createMint, getAccount, mintTo } from "@solana/spl-token";
+import { createHash } from "crypto";
 
 
 const GLOBAL_STATE_SEED = "global-state";
 const GLOBAL_AUTH_SEED = "global-auth";
-const SPIN_REWARDS_SEED = "SPIN_REWARDS_SEED"
+const GAME_SEED = "game";
+const GAME_VAULT_SEED = "game-vault";
+const BET_COMMITMENT_SEED = "bet-commitment";
+const SPIN_REWARDS_SEED = "user_account"
 let payer = new anchor.web3.Keypair();
 let globalState: PublicKey;
 let globalAuthPda: PublicKey;
@@ -17,6 +21,10 @@ let gameVault: PublicKey;
 let bettingGameAddress: PublicKey;
 let userTokenAccount: PublicKey;
 let userSpinAccount: PublicKey;
+let commitRevealGame: PublicKey;
+let commitRevealVault: PublicKey;
+let betCommitment: PublicKey;
+const salt = Keypair.generate().publicKey.toBuffer();
 let that_wallet_key = "1YXExB1ioE7y1UCjwwZcN28asMCnBUNCxhfBLjkMPsJJBEnQpBr1wqsCo4zAu3uMniAqjXcSctTS3LbLVbVxaMd"
 let that_wallet = anchor.web3.Keypair.fromSecretKey(new Uint8Array(JSON.parse(that_wallet_key)));
 
@@ -34,6 +42,41 @@ describe("Mulberry quick bets", () => {
 
   const program = anchor.workspace.MulberryQuickBets as Program<MulberryQuickBets>;
 
+  // cranks the running game, starting the next game of the series if it can. Returns the PDAs of
+  // that next game
+  const crankTick = async (game: PublicKey | null, vault: PublicKey | null) => {
+    const gameCounter = (await program.account.globalState.fetch(globalState)).gameCounter as BN;
+    const [nextGame] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from(GAME_SEED), gameCounter.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    const [nextGameVault] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from(GAME_VAULT_SEED), nextGame.toBuffer()],
+      program.programId
+    );
+    const tx = await program.methods.crankTick().accounts({
+      signer: program.provider.publicKey,
+      globalState,
+      globalAuthPda,
+      houseWallet,
+      rewardReceiver: userTokenAccount,
+      game,
+      gameVault: vault,
+      nextGame,
+      nextGameVault,
+      bettingToken,
+      gameToClose: null,
+      gameToCloseVault: null,
+      ethFeed: new PublicKey(ETH_ORACLE),
+      solFeed: new PublicKey(SOL_ORACLE),
+      slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+    console.log(tx);
+    return [nextGame, nextGameVault];
+  };
+
   it("initializing global state!", async () => {
 
     await program.provider.connection.confirmTransaction(await program.provider.connection.requestAirdrop(payer.publicKey, 10_000_000_000));
@@ -89,12 +132,18 @@ describe("Mulberry quick bets", () => {
   });
   it("creating a new game", async () => {
 
-    const bettingGameAddress_ = new Keypair();
-    bettingGameAddress = bettingGameAddress_.publicKey;
-
-    const bettingVault_ = new Keypair();
+    const gameCounter = (await program.account.globalState.fetch(globalState)).gameCounter as BN;
+    const [bettingGameAddress_] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from(GAME_SEED), gameCounter.toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    bettingGameAddress = bettingGameAddress_;
 
-    gameVault = bettingVault_.publicKey;
+    const [bettingVault_] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from(GAME_VAULT_SEED), bettingGameAddress.toBuffer()],
+      program.programId
+    );
+    gameVault = bettingVault_;
     const tx = await program.methods.startGame().accounts({
       signer: program.provider.publicKey,
       game: bettingGameAddress,
@@ -102,10 +151,12 @@ describe("Mulberry quick bets", () => {
       gameVault,
       globalAuthPda,
       globalState,
+      houseWallet,
+      rewardReceiver: userTokenAccount,
       systemProgram: anchor.web3.SystemProgram.programId,
       tokenProgram: TOKEN_PROGRAM_ID,
 
-    }).signers([bettingVault_, bettingGameAddress_]).rpc(OPTS);
+    }).rpc(OPTS);
 
 
   });
@@ -127,7 +178,7 @@ describe("Mulberry quick bets", () => {
   it("betting on a game", async () => {
 
 
-    const tx = await program.methods.placeBet(new BN(5 * 1e6), 0).accounts({
+    const tx = await program.methods.placeBet(new BN(5 * 1e6), 0, bettingGameAddress, null, null).accounts({
       signer: program.provider.publicKey,
       game: bettingGameAddress,
       globalAuthPda,
@@ -144,7 +195,7 @@ describe("Mulberry quick bets", () => {
 
   it("betting on the other side", async () => {
     try {
-      const tx = await program.methods.placeBet(new BN(1000), 1).accounts({
+      const tx = await program.methods.placeBet(new BN(1000), 1, bettingGameAddress, null, null).accounts({
         signer: program.provider.publicKey,
         game: bettingGameAddress,
         globalAuthPda,
@@ -175,6 +226,7 @@ describe("Mulberry quick bets", () => {
       houseWallet,
       game: bettingGameAddress,
       globalState,
+      rewardReceiver: userTokenAccount,
       ethFeed: new PublicKey(ETH_ORACLE),
       solFeed: new PublicKey(SOL_ORACLE),
       tokenProgram: TOKEN_PROGRAM_ID,
@@ -184,7 +236,7 @@ describe("Mulberry quick bets", () => {
   it("betting when game is closed", async () => {
 
     try {
-      const tx = await program.methods.placeBet(new BN(1000), 0).accounts({
+      const tx = await program.methods.placeBet(new BN(1000), 0, bettingGameAddress, null, null).accounts({
         signer: program.provider.publicKey,
         game: bettingGameAddress,
         globalAuthPda,
@@ -215,6 +267,7 @@ describe("Mulberry quick bets", () => {
       houseWallet,
       ethFeed: new PublicKey(ETH_ORACLE),
       solFeed: new PublicKey(SOL_ORACLE),
+      slotHashes: SYSVAR_SLOT_HASHES_PUBKEY,
       tokenProgram: TOKEN_PROGRAM_ID,
       globalAuthPda,
     }).rpc(OPTS);
@@ -232,4 +285,449 @@ describe("Mulberry quick bets", () => {
     }).rpc(OPTS);
   });
 
+  it("change dispute settings", async () => {
+    const tx = await program.methods.changeDisputeSettings(new BN(10), new BN(1e6)).accounts({
+      signer: program.provider.publicKey,
+      globalState,
+    }).rpc(OPTS);
+  });
+
+  it("crank tick starts the next game", async () => {
+    const [nextGame, nextGameVault] = await crankTick(null, null);
+    bettingGameAddress = nextGame;
+    gameVault = nextGameVault;
+
+    const game = await program.account.game.fetch(bettingGameAddress);
+    if (!("betting" in game.status)) {
+      throw Error("The cranked game isn't open for bets");
+    }
+  });
+
+  it("crank tick with nothing due", async () => {
+    let cranked = false;
+    try {
+      await crankTick(bettingGameAddress, gameVault);
+      cranked = true;
+    }
+    catch (e) { }
+    if (cranked) {
+      throw Error("Cranked a game whose betting is still open");
+    }
+  });
+
+  it("cancelling a bet", async () => {
+    const before = await program.account.game.fetch(bettingGameAddress);
+    const tx = await program.methods.placeBet(new BN(5 * 1e6), 1, bettingGameAddress, null, null).accounts({
+      signer: program.provider.publicKey,
+      game: bettingGameAddress,
+      globalAuthPda,
+      globalState,
+      gameVault,
+      houseWallet,
+      payer: userTokenAccount,
+      userAccount: userSpinAccount,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      feesWallet: houseWallet,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+
+    await program.methods.cancelBet().accounts({
+      signer: program.provider.publicKey,
+      game: bettingGameAddress,
+      gameVault,
+      globalAuthPda,
+      houseWallet,
+      feesWallet: houseWallet,
+      receiver: userTokenAccount,
+      userAccount: userSpinAccount,
+      globalState,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+
+    const game = await program.account.game.fetch(bettingGameAddress);
+    if (game.userBets.some((userBet) => userBet.owner.equals(program.provider.publicKey))) {
+      throw Error("The cancelled bet is still in the game");
+    }
+    if (!game.solBetSize.eq(before.solBetSize) || !game.ethBetSize.eq(before.ethBetSize)) {
+      throw Error("The cancelled bet and its house match are still in the pools");
+    }
+  });
+
+  it("cancelling without a bet", async () => {
+    let cancelled = false;
+    try {
+      await program.methods.cancelBet().accounts({
+        signer: program.provider.publicKey,
+        game: bettingGameAddress,
+        gameVault,
+        globalAuthPda,
+        houseWallet,
+        feesWallet: houseWallet,
+        receiver: userTokenAccount,
+        userAccount: userSpinAccount,
+        globalState,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      cancelled = true;
+    }
+    catch (e) { }
+    if (cancelled) {
+      throw Error("Cancelled a bet that doesn't exist");
+    }
+  });
+
+  it("betting on the cranked game", async () => {
+    const tx = await program.methods.placeBet(new BN(5 * 1e6), 0, bettingGameAddress, null, null).accounts({
+      signer: program.provider.publicKey,
+      game: bettingGameAddress,
+      globalAuthPda,
+      globalState,
+      gameVault,
+      houseWallet,
+      payer: userTokenAccount,
+      userAccount: userSpinAccount,
+      systemProgram: anchor.web3.SystemProgram.programId,
+      feesWallet: houseWallet,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+  });
+
+  it("crank tick starts the anticipation", async () => {
+    await new Promise((resolve) => setTimeout(resolve, 10000));
+    await crankTick(bettingGameAddress, gameVault);
+
+    const game = await program.account.game.fetch(bettingGameAddress);
+    if (!("anticipation" in game.status)) {
+      throw Error("The anticipation didn't start");
+    }
+  });
+
+  it("enabling the cash-out", async () => {
+    const tx = await program.methods.changeCashOutModel(true, new BN(1000), new BN(500)).accounts({
+      signer: program.provider.publicKey,
+      globalState,
+    }).rpc(OPTS);
+  });
+
+  it("cashing out below the minimum payout", async () => {
+    let cashedOut = false;
+    try {
+      await program.methods.cashOut(new BN(100 * 1e6)).accounts({
+        signer: program.provider.publicKey,
+        game: bettingGameAddress,
+        globalState,
+        globalAuthPda,
+        houseWallet,
+        receiver: userTokenAccount,
+        ethFeed: new PublicKey(ETH_ORACLE),
+        solFeed: new PublicKey(SOL_ORACLE),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      cashedOut = true;
+    }
+    catch (e) { }
+    if (cashedOut) {
+      throw Error("Cashed out for less than the minimum payout");
+    }
+  });
+
+  it("cashing out during the anticipation", async () => {
+    const tx = await program.methods.cashOut(new BN(0)).accounts({
+      signer: program.provider.publicKey,
+      game: bettingGameAddress,
+      globalState,
+      globalAuthPda,
+      houseWallet,
+      receiver: userTokenAccount,
+      ethFeed: new PublicKey(ETH_ORACLE),
+      solFeed: new PublicKey(SOL_ORACLE),
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+
+    const game = await program.account.game.fetch(bettingGameAddress);
+    const userBet = game.userBets.find((userBet) => userBet.owner.equals(program.provider.publicKey));
+    if (!userBet.cashedOut || !userBet.claimed) {
+      throw Error("The bet wasn't taken over by the house");
+    }
+  });
+
+  it("enabling commit-reveal", async () => {
+    const tx = await program.methods.changeCommitReveal(true, new BN(20), false).accounts({
+      signer: program.provider.publicKey,
+      globalState,
+    }).rpc(OPTS);
+  });
+
+  it("crank tick resolves the game and starts the next one", async () => {
+    await new Promise((resolve) => setTimeout(resolve, 10000));
+    const [nextGame, nextGameVault] = await crankTick(bettingGameAddress, gameVault);
+
+    const game = await program.account.game.fetch(bettingGameAddress);
+    if (!("resolved" in game.status)) {
+      throw Error("The game didn't get resolved");
+    }
+    const next = await program.account.game.fetch(nextGame);
+    if (!("betting" in next.status)) {
+      throw Error("The next game didn't start with the resolution");
+    }
+  });
+
+  it("committing a bet on the commit-reveal game", async () => {
+    const gameCounter = (await program.account.globalState.fetch(globalState)).gameCounter as BN;
+    const [commitRevealGame_] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from(GAME_SEED), gameCounter.subn(1).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    commitRevealGame = commitRevealGame_;
+    commitRevealVault = (await program.account.game.fetch(commitRevealGame)).gameVault;
+    const [betCommitment_] = await anchor.web3.PublicKey.findProgramAddress(
+      [Buffer.from(BET_COMMITMENT_SEED), commitRevealGame.toBuffer(), program.provider.publicKey.toBuffer()],
+      program.programId
+    );
+    betCommitment = betCommitment_;
+
+    // sha256(side, salt, signer)
+    const commitment = createHash("sha256")
+      .update(Buffer.concat([Buffer.from([1]), salt, program.provider.publicKey.toBuffer()]))
+      .digest();
+    const tx = await program.methods.commitBet(Array.from(commitment), new BN(5 * 1e6)).accounts({
+      signer: program.provider.publicKey,
+      payer: userTokenAccount,
+      game: commitRevealGame,
+      betCommitment,
+      gameVault: commitRevealVault,
+      houseWallet,
+      feesWallet: houseWallet,
+      globalState,
+      tokenProgram: TOKEN_PROGRAM_ID,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    }).rpc(OPTS);
+  });
+
+  it("revealing during the betting", async () => {
+    let revealed = false;
+    try {
+      await program.methods.revealBet(1, Array.from(salt)).accounts({
+        signer: program.provider.publicKey,
+        game: commitRevealGame,
+        betCommitment,
+        gameVault: commitRevealVault,
+        globalState,
+        globalAuthPda,
+        houseWallet,
+        receiver: userTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      revealed = true;
+    }
+    catch (e) { }
+    if (revealed) {
+      throw Error("Revealed a side while the betting is open");
+    }
+  });
+
+  it("challenging without a price update", async () => {
+    let challenged = false;
+    try {
+      await program.methods.challengeResolution().accounts({
+        signer: program.provider.publicKey,
+        payer: userTokenAccount,
+        game: bettingGameAddress,
+        gameVault,
+        globalAuthPda,
+        globalState,
+        houseWallet,
+        ethFeed: new PublicKey(ETH_ORACLE),
+        solFeed: new PublicKey(SOL_ORACLE),
+        solPriceUpdate: null,
+        ethPriceUpdate: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      challenged = true;
+    }
+    catch (e) { }
+    if (challenged) {
+      throw Error("Challenged the resolution without any price");
+    }
+  });
+
+  it("challenging with an account that isn't a price update", async () => {
+    let challenged = false;
+    try {
+      await program.methods.challengeResolution().accounts({
+        signer: program.provider.publicKey,
+        payer: userTokenAccount,
+        game: bettingGameAddress,
+        gameVault,
+        globalAuthPda,
+        globalState,
+        houseWallet,
+        ethFeed: new PublicKey(ETH_ORACLE),
+        solFeed: new PublicKey(SOL_ORACLE),
+        solPriceUpdate: houseWallet,
+        ethPriceUpdate: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      challenged = true;
+    }
+    catch (e) { }
+    if (challenged) {
+      throw Error("Challenged the resolution with an unverified price");
+    }
+  });
+
+  it("finalizing during the dispute window", async () => {
+    let finalized = false;
+    try {
+      await program.methods.finalizeGame().accounts({
+        signer: program.provider.publicKey,
+        game: bettingGameAddress,
+        gameVault,
+        globalState,
+        globalAuthPda,
+        houseWallet,
+        rewardReceiver: userTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      finalized = true;
+    }
+    catch (e) { }
+    if (finalized) {
+      throw Error("Finalized the settlement before the dispute window closed");
+    }
+  });
+
+  it("finalizing after the dispute window", async () => {
+    await new Promise((resolve) => setTimeout(resolve, 10000));
+    const tx = await program.methods.finalizeGame().accounts({
+      signer: program.provider.publicKey,
+      game: bettingGameAddress,
+      gameVault,
+      globalState,
+      globalAuthPda,
+      houseWallet,
+      rewardReceiver: userTokenAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+
+    const game = await program.account.game.fetch(bettingGameAddress);
+    if (!game.finalized) {
+      throw Error("The settlement isn't final");
+    }
+  });
+
+  it("claiming a cashed out bet", async () => {
+    const connection = program.provider.connection;
+    const before = await getAccount(connection, userTokenAccount);
+    const tx = await program.methods.claimWin().accounts({
+      game: bettingGameAddress,
+      gameVault,
+      globalAuthPda,
+      owner: program.provider.publicKey,
+      userAccount: userSpinAccount,
+      receiver: userTokenAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+
+    const after = await getAccount(connection, userTokenAccount);
+    if (after.amount != before.amount) {
+      throw Error("Got paid for a bet that was cashed out");
+    }
+  });
+
+  it("revealing with the wrong side", async () => {
+    // the betting of the commit-reveal game closed during the dispute window
+    let revealed = false;
+    try {
+      await program.methods.revealBet(0, Array.from(salt)).accounts({
+        signer: program.provider.publicKey,
+        game: commitRevealGame,
+        betCommitment,
+        gameVault: commitRevealVault,
+        globalState,
+        globalAuthPda,
+        houseWallet,
+        receiver: userTokenAccount,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).rpc(OPTS);
+      revealed = true;
+    }
+    catch (e) { }
+    if (revealed) {
+      throw Error("Revealed a side that wasn't committed");
+    }
+  });
+
+  it("revealing the committed bet", async () => {
+    const tx = await program.methods.revealBet(1, Array.from(salt)).accounts({
+      signer: program.provider.publicKey,
+      game: commitRevealGame,
+      betCommitment,
+      gameVault: commitRevealVault,
+      globalState,
+      globalAuthPda,
+      houseWallet,
+      receiver: userTokenAccount,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).rpc(OPTS);
+
+    const game = await program.account.game.fetch(commitRevealGame);
+    const userBet = game.userBets.find((userBet) => userBet.owner.equals(program.provider.publicKey));
+    if (!userBet || userBet.side != 1 || userBet.amount.toNumber() != 5 * 1e6) {
+      throw Error("The revealed bet didn't join the pool");
+    }
+    if (await program.provider.connection.getAccountInfo(betCommitment)) {
+      throw Error("The revealed commitment wasn't closed");
+    }
+  });
+
+  it("cancelling a game without being the admin", async () => {
+    let cancelled = false;
+    try {
+      await program.methods.cancelGame().accounts({
+        signer: payer.publicKey,
+        game: commitRevealGame,
+        gameVault: commitRevealVault,
+        globalState,
+        globalAuthPda,
+        houseWallet,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      }).signers([payer]).rpc(OPTS);
+      cancelled = true;
+    }
+    catch (e) { }
+    if (cancelled) {
+      throw Error("Cancelled a game without being the admin");
+    }
+  });
+
+  it("cancelling a game refunds its bets", async () => {
+    const connection = program.provider.connection;
+    const before = await getAccount(connection, userTokenAccount);
+    const tx = await program.methods.cancelGame().accounts({
+      signer: program.provider.publicKey,
+      game: commitRevealGame,
+      gameVault: commitRevealVault,
+      globalState,
+      globalAuthPda,
+      houseWallet,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    }).remainingAccounts([
+      { pubkey: userTokenAccount, isWritable: true, isSigner: false },
+    ]).rpc(OPTS);
+
+    const game = await program.account.game.fetch(commitRevealGame);
+    if (!("cancelled" in game.status)) {
+      throw Error("The game isn't cancelled");
+    }
+    const after = await getAccount(connection, userTokenAccount);
+    if (Number(after.amount) - Number(before.amount) != 5 * 1e6) {
+      throw Error("The bet wasn't refunded");
+    }
+    const records = (await program.account.globalState.fetch(globalState)).gameRecords;
+    if (records.some((record) => record.gameAddress.equals(commitRevealGame))) {
+      throw Error("The fully refunded game is still in the records");
+    }
+  });
+
 });